@@ -0,0 +1,50 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rust_nitro_sniper::store::{MemoryStore, SeenCode, SeenCodeStore};
+use std::time::Duration;
+
+const TTL: Duration = Duration::from_secs(72 * 60 * 60);
+
+fn filled_store(size: usize) -> MemoryStore {
+    let mut store = MemoryStore::new(size, TTL);
+    for i in 0..size {
+        store.insert(SeenCode::new(
+            format!("{:0>16}", i),
+            "bench#0000".to_string(),
+        ));
+    }
+    store
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("seen_codes_lookup");
+    for size in [1_000, 10_000, 100_000, 1_000_000].iter() {
+        let store = filled_store(*size);
+        let hit = format!("{:0>16}", size / 2);
+        let miss = "ThisCodeWasNeverSeen".to_string();
+
+        group.bench_with_input(BenchmarkId::new("hit", size), &hit, |b, code| {
+            b.iter(|| store.contains(black_box(code)))
+        });
+        group.bench_with_input(BenchmarkId::new("miss", size), &miss, |b, code| {
+            b.iter(|| store.contains(black_box(code)))
+        });
+    }
+    group.finish();
+}
+
+fn insert_at_capacity(c: &mut Criterion) {
+    let mut store = filled_store(100_000);
+    let mut i = 0_usize;
+    c.bench_function("seen_codes_insert_at_capacity", |b| {
+        b.iter(|| {
+            i += 1;
+            store.insert(SeenCode::new(
+                format!("new{:0>16}", i),
+                "bench#0000".to_string(),
+            ));
+        })
+    });
+}
+
+criterion_group!(benches, lookup, insert_at_capacity);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rust_nitro_sniper::scorer::ScorerKind;
+
+fuzz_target!(|code: &str| {
+    for kind in &ScorerKind::ALL {
+        let score = kind.build().score(code);
+        assert!((0.0..=1.0).contains(&score), "{} scored {}", kind, score);
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rust_nitro_sniper::matcher::Matcher;
+
+fuzz_target!(|text: &str| {
+    let matcher = Matcher::default();
+    for gift_match in matcher.score_gift_codes_in(std::iter::once(text)) {
+        assert!((0.0..=1.0).contains(&gift_match.score));
+        assert!(text.len() >= gift_match.code.len());
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rust_nitro_sniper::matcher::sanitize_markdown;
+
+fuzz_target!(|text: &str| {
+    let once = sanitize_markdown(text);
+    assert!(once.len() <= text.len());
+    assert_eq!(sanitize_markdown(&once), once);
+});
@@ -50,6 +50,7 @@ impl Location {
     }
 }
 
+/// Caches guild and channel names so we only have to request them once.
 pub struct LocationCache {
     channel_map: Mutex<HashMap<ChannelId, Location>>,
     guild_map: Mutex<HashMap<GuildId, Arc<String>>>,
@@ -0,0 +1,82 @@
+use log::LevelFilter;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "rns", about = "=^.^= A simple and easy to use Nitro sniper.")]
+pub struct Options {
+    /// Path to the config file. Ends in .json, .toml, .yaml or .yml.
+    #[structopt(long, parse(from_os_str), default_value = "rns-config.json")]
+    pub config: PathBuf,
+
+    /// Disables colored output.
+    #[structopt(long)]
+    pub no_color: bool,
+
+    /// Exits right away on fatal errors instead of waiting for the enter key.
+    /// This is the default when stdin isn't a terminal.
+    #[structopt(long)]
+    pub headless: bool,
+
+    /// One of off, error, warn, info, debug or trace.
+    #[structopt(long, default_value = "info")]
+    pub log_level: LevelFilter,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+pub enum Command {
+    /// Connects to all accounts and starts sniping. This is the default.
+    Run,
+    /// Reads the config file and reports whether it's usable.
+    CheckConfig,
+    /// Checks every token in the config against Discord.
+    VerifyTokens,
+    /// Writes the default config file in the format of its extension.
+    Init {
+        /// Overwrites an existing config file.
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Lists every gift code the matcher finds in some text.
+    TestMatch { text: String },
+    /// Measures the matcher against a JSONL file of labelled messages.
+    ///
+    /// Each line looks like {"content": "...", "label": "real_code"}, with
+    /// "not_a_code" as the other label.
+    EvalMatcher {
+        #[structopt(parse(from_os_str))]
+        corpus: PathBuf,
+    },
+    /// Manages the encrypted token vault.
+    Vault {
+        /// Path to the vault file.
+        #[structopt(long, parse(from_os_str), default_value = "rns-vault.json")]
+        path: PathBuf,
+
+        /// Reads the passphrase from this file instead of asking for it.
+        #[structopt(long, parse(from_os_str))]
+        key_file: Option<PathBuf>,
+
+        #[structopt(subcommand)]
+        action: VaultAction,
+    },
+}
+
+#[derive(StructOpt)]
+pub enum VaultAction {
+    /// Adds a token under a label. The token is read from the terminal or stdin.
+    Add {
+        label: String,
+
+        /// Makes this the main token, the one receiving the Nitro.
+        #[structopt(long)]
+        main: bool,
+    },
+    /// Removes the token with this label.
+    Remove { label: String },
+    /// Lists the labels of all stored tokens.
+    List,
+}
@@ -0,0 +1,177 @@
+use crate::discord::{Handler, HandlerInfo};
+use crate::pretty_error;
+use crate::token::SecretToken;
+use colored::*;
+use futures::future::BoxFuture;
+use serenity::async_trait;
+use serenity::client::bridge::gateway::ShardManager;
+use serenity::Client as DiscordClient;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Mutex, Notify};
+
+/// Stops a running client, making its `Connection::run` resolve.
+#[async_trait]
+pub trait Shutdown: Send + Sync {
+    async fn shutdown(&self);
+}
+
+#[async_trait]
+impl Shutdown for Arc<Mutex<ShardManager>> {
+    async fn shutdown(&self) {
+        self.lock().await.shutdown_all().await;
+    }
+}
+
+/// A client that's set up, but not started yet.
+pub struct Connection {
+    /// Runs the client until it disconnects, resolving to whether it ever connected.
+    pub run: BoxFuture<'static, bool>,
+    pub shutdown: Box<dyn Shutdown>,
+}
+
+/// Sets up the client for a token.
+#[async_trait]
+pub trait Connector: Send + Sync {
+    async fn connect(&self, token: &SecretToken, info: Arc<HandlerInfo>) -> Option<Connection>;
+}
+
+/// Connects to Discord's gateway with serenity.
+pub struct DiscordConnector;
+
+#[async_trait]
+impl Connector for DiscordConnector {
+    async fn connect(&self, token: &SecretToken, info: Arc<HandlerInfo>) -> Option<Connection> {
+        let mut discord_client = DiscordClient::builder(token.expose())
+            .event_handler(Handler::new(info, token.clone()))
+            .await
+            .ok()?;
+
+        let shard_manager = discord_client.shard_manager.clone();
+        Some(Connection {
+            run: Box::pin(async move { discord_client.start().await.is_ok() }),
+            shutdown: Box::new(shard_manager),
+        })
+    }
+}
+
+struct RunningClient {
+    id: usize,
+    shutdown: Box<dyn Shutdown>,
+}
+
+/// Keeps one running Discord client per sniping token.
+pub struct ClientPool {
+    info: Arc<HandlerInfo>,
+    connector: Box<dyn Connector>,
+    clients: Mutex<HashMap<SecretToken, RunningClient>>,
+    next_id: AtomicUsize,
+    emptied: Notify,
+}
+
+impl ClientPool {
+    pub fn new(info: Arc<HandlerInfo>) -> Arc<Self> {
+        Self::with_connector(info, Box::new(DiscordConnector))
+    }
+
+    pub fn with_connector(info: Arc<HandlerInfo>, connector: Box<dyn Connector>) -> Arc<Self> {
+        Arc::new(ClientPool {
+            info,
+            connector,
+            clients: Mutex::new(HashMap::new()),
+            next_id: AtomicUsize::new(0),
+            emptied: Notify::new(),
+        })
+    }
+
+    /// Starts clients for new tokens and stops the ones whose token is gone,
+    /// leaving every other connection alone.
+    ///
+    /// New clients are started first, so the pool is never empty halfway through
+    /// a reload that swaps out every token.
+    pub async fn sync(self: &Arc<Self>, tokens: Vec<SecretToken>) {
+        let running = self.tokens().await;
+
+        for token in tokens.iter().filter(|t| !running.contains(t)) {
+            self.start(token.clone()).await;
+        }
+        for token in running.iter().filter(|t| !tokens.contains(t)) {
+            self.stop(token).await;
+        }
+    }
+
+    pub async fn start(self: &Arc<Self>, token: SecretToken) {
+        let label = token.label();
+        let connection = match self.connector.connect(&token, self.info.clone()).await {
+            Some(connection) => connection,
+            None => {
+                pretty_error!(
+                    "(-_-;)°°°",
+                    "Couldn't instantiate a Discord client for {}.",
+                    label,
+                );
+                return;
+            }
+        };
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.clients.lock().await.insert(
+            token.clone(),
+            RunningClient {
+                id,
+                shutdown: connection.shutdown,
+            },
+        );
+
+        let pool = self.clone();
+        let run = connection.run;
+        tokio::spawn(async move {
+            if !run.await {
+                pretty_error!(
+                    "(＃`Д´)",
+                    "Couldn't make a connection to Discord on {}. Is your token correct?",
+                    label
+                );
+            }
+            pool.remove(&token, Some(id)).await;
+        });
+    }
+
+    pub async fn stop(&self, token: &SecretToken) {
+        if let Some(client) = self.remove(token, None).await {
+            client.shutdown.shutdown().await;
+        }
+    }
+
+    /// The tokens that currently have a client.
+    pub async fn tokens(&self) -> Vec<SecretToken> {
+        self.clients.lock().await.keys().cloned().collect()
+    }
+
+    /// Removes a client, but only if it's still the one with the given id.
+    async fn remove(&self, token: &SecretToken, id: Option<usize>) -> Option<RunningClient> {
+        let mut clients = self.clients.lock().await;
+        let removed = match clients.get(token) {
+            Some(client) if id.map_or(true, |id| id == client.id) => clients.remove(token),
+            _ => None,
+        };
+        if removed.is_some() {
+            self.info.account_disconnected(token);
+        }
+        if clients.is_empty() {
+            self.emptied.notify();
+        }
+        removed
+    }
+
+    /// Resolves once no client is running anymore.
+    pub async fn wait_until_empty(&self) {
+        loop {
+            if self.clients.lock().await.is_empty() {
+                return;
+            }
+            self.emptied.notified().await;
+        }
+    }
+}
@@ -1,36 +1,175 @@
+use crate::gift::GiftInfoLookup;
+use crate::logging::ExitCode;
+use crate::matcher::{self, GiftPattern, Matcher};
+use crate::scorer::ScorerKind;
+use crate::token::SecretToken;
+use crate::vault::{self, Vault, VaultError};
 use crate::{log_error_and_exit, pretty_error};
 use colored::*;
+use hyper::Uri;
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use serde_json::Value;
 use serenity::model::id::GuildId;
-use std::fs::File;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+/// Snowflakes below this would have been created in the first millisecond of Discord's epoch.
+const MIN_SNOWFLAKE: u64 = 1 << 22;
+
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+
+/// The contents of the config file, `rns-config.json` by default.
 #[derive(Deserialize, Serialize)]
 pub struct Config {
-    main_token: String,
+    #[serde(default)]
+    main_token: SecretToken,
     snipe_on_main_token: bool,
-    sub_tokens: Vec<String>,
+    #[serde(default)]
+    sub_tokens: Vec<SecretToken>,
+    #[serde(default)]
     webhook: String,
     guild_blacklist: Vec<u64>,
+    #[serde(default = "default_api_base")]
+    api_base: String,
+    #[serde(default = "default_api_version")]
+    api_version: u8,
+    #[serde(default)]
+    seen_codes_file: Option<String>,
+    #[serde(default = "default_seen_codes_capacity")]
+    seen_codes_capacity: usize,
+    #[serde(default = "default_seen_codes_ttl_hours")]
+    seen_codes_ttl_hours: u64,
+    #[serde(default = "default_redeem_concurrency")]
+    redeem_concurrency: usize,
+    #[serde(default = "default_redeem_timeout_ms")]
+    redeem_timeout_ms: u64,
+    #[serde(default)]
+    gift_info_lookup: GiftInfoLookup,
+    #[serde(default)]
+    stats_file: Option<String>,
+    #[serde(default)]
+    http2: bool,
+    #[serde(default = "default_keep_alive_seconds")]
+    keep_alive_seconds: u64,
+    #[serde(default)]
+    vault_file: Option<String>,
+    #[serde(default)]
+    vault_key_file: Option<String>,
+    #[serde(default)]
+    gift_patterns: Option<Vec<GiftPattern>>,
+    #[serde(default)]
+    scorer: ScorerKind,
+    #[serde(default)]
+    score_threshold: Option<f64>,
+    #[serde(skip)]
+    matcher: Matcher,
+    #[serde(skip)]
+    unknown_keys: Vec<String>,
+}
+
+fn default_api_base() -> String {
+    "https://discordapp.com/api".to_string()
+}
+
+fn default_api_version() -> u8 {
+    8
+}
+
+fn default_seen_codes_capacity() -> usize {
+    100_000
+}
+
+fn default_seen_codes_ttl_hours() -> u64 {
+    72
+}
+
+fn default_redeem_concurrency() -> usize {
+    4
+}
+
+fn default_redeem_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_keep_alive_seconds() -> u64 {
+    30
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
-            main_token: "YOUR_TOKEN_HERE".to_string(),
+            main_token: SecretToken::new("YOUR_TOKEN_HERE"),
             snipe_on_main_token: true,
             sub_tokens: Vec::new(),
             webhook: "".to_string(),
             guild_blacklist: Vec::new(),
+            api_base: default_api_base(),
+            api_version: default_api_version(),
+            seen_codes_file: None,
+            seen_codes_capacity: default_seen_codes_capacity(),
+            seen_codes_ttl_hours: default_seen_codes_ttl_hours(),
+            redeem_concurrency: default_redeem_concurrency(),
+            redeem_timeout_ms: default_redeem_timeout_ms(),
+            gift_info_lookup: GiftInfoLookup::default(),
+            stats_file: None,
+            http2: false,
+            keep_alive_seconds: default_keep_alive_seconds(),
+            vault_file: None,
+            vault_key_file: None,
+            gift_patterns: None,
+            scorer: ScorerKind::default(),
+            score_threshold: None,
+            matcher: Matcher::default(),
+            unknown_keys: Vec::new(),
         }
     }
 }
 
+/// Safe to log: tokens print their labels and the webhook URL, which holds its own token, is hidden.
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let webhook = if self.webhook.is_empty() {
+            ""
+        } else {
+            "<redacted>"
+        };
+        f.debug_struct("Config")
+            .field("main_token", &self.main_token)
+            .field("snipe_on_main_token", &self.snipe_on_main_token)
+            .field("sub_tokens", &self.sub_tokens)
+            .field("webhook", &webhook)
+            .field("guild_blacklist", &self.guild_blacklist)
+            .field("api_base", &self.api_base)
+            .field("api_version", &self.api_version)
+            .field("seen_codes_file", &self.seen_codes_file)
+            .field("seen_codes_capacity", &self.seen_codes_capacity)
+            .field("seen_codes_ttl_hours", &self.seen_codes_ttl_hours)
+            .field("redeem_concurrency", &self.redeem_concurrency)
+            .field("redeem_timeout_ms", &self.redeem_timeout_ms)
+            .field("gift_info_lookup", &self.gift_info_lookup)
+            .field("stats_file", &self.stats_file)
+            .field("http2", &self.http2)
+            .field("keep_alive_seconds", &self.keep_alive_seconds)
+            .field("vault_file", &self.vault_file)
+            .field("vault_key_file", &self.vault_key_file)
+            .field("gift_patterns", &self.gift_patterns)
+            .field("scorer", &self.scorer)
+            .field("score_threshold", &self.score_threshold)
+            .finish()
+    }
+}
+
 impl Config {
-    pub fn main_token(&self) -> String {
+    pub fn main_token(&self) -> SecretToken {
         self.main_token.clone()
     }
 
-    pub fn get_all_sniping_tokens(&self) -> Vec<String> {
+    pub fn get_all_sniping_tokens(&self) -> Vec<SecretToken> {
         let mut tokens = self.sub_tokens.clone();
         if self.snipe_on_main_token {
             tokens.insert(0, self.main_token());
@@ -46,32 +185,367 @@ impl Config {
         }
     }
 
+    pub fn seen_codes_file(&self) -> Option<String> {
+        self.seen_codes_file.clone()
+    }
+
+    pub fn seen_codes_capacity(&self) -> usize {
+        self.seen_codes_capacity
+    }
+
+    pub fn seen_codes_ttl(&self) -> Duration {
+        Duration::from_secs(self.seen_codes_ttl_hours.saturating_mul(SECONDS_PER_HOUR))
+    }
+
+    /// How many codes can be redeemed at the same time.
+    pub fn redeem_concurrency(&self) -> usize {
+        self.redeem_concurrency
+    }
+
+    /// How long a single redeem attempt may take before it's given up.
+    pub fn redeem_timeout(&self) -> Duration {
+        Duration::from_millis(self.redeem_timeout_ms)
+    }
+
+    pub fn gift_info_lookup(&self) -> GiftInfoLookup {
+        self.gift_info_lookup
+    }
+
+    /// Where to append the timings of every claim attempt, if anywhere.
+    pub fn stats_file(&self) -> Option<String> {
+        self.stats_file.clone()
+    }
+
+    pub fn http2(&self) -> bool {
+        self.http2
+    }
+
+    /// How often to ping the API to keep connections open, if at all.
+    pub fn keep_alive_interval(&self) -> Option<Duration> {
+        if self.keep_alive_seconds == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.keep_alive_seconds))
+        }
+    }
+
+    pub fn api_url(&self, route: &str) -> String {
+        format!(
+            "{}/v{}{}",
+            self.api_base.trim_end_matches('/'),
+            self.api_version,
+            route
+        )
+    }
+
+    /// Takes the tokens from the encrypted vault if one is configured.
+    ///
+    /// The entry marked as main becomes the main token and every other entry a sub-token.
+    pub fn apply_vault(&mut self) -> Result<(), ConfigReadError> {
+        let path = match &self.vault_file {
+            Some(path) => PathBuf::from(path),
+            None => return Ok(()),
+        };
+        let key_file = self.vault_key_file.as_ref().map(PathBuf::from);
+
+        // Remembered so reloading the config doesn't ask for the passphrase again.
+        static PASSPHRASE: OnceCell<String> = OnceCell::new();
+        let passphrase = match PASSPHRASE.get() {
+            Some(passphrase) => passphrase.clone(),
+            None => {
+                let passphrase = match read_env_secret("RNS_VAULT_PASSPHRASE")? {
+                    Some(passphrase) => passphrase,
+                    None => vault::read_passphrase(key_file.as_deref())
+                        .map_err(ConfigReadError::Vault)?,
+                };
+                PASSPHRASE.get_or_init(|| passphrase).clone()
+            }
+        };
+        let vault = Vault::open(&path, passphrase).map_err(ConfigReadError::Vault)?;
+
+        if let Some(token) = vault.main_token() {
+            self.main_token = token;
+        }
+        self.sub_tokens = vault.sub_tokens();
+        Ok(())
+    }
+
+    /// Overrides secrets with values from the environment.
+    ///
+    /// For each of `RNS_MAIN_TOKEN`, `RNS_SUB_TOKENS` and `RNS_WEBHOOK` the variable itself
+    /// wins over a `*_FILE` variant pointing at a secrets file, which wins over the config file.
+    /// `RNS_SUB_TOKENS` takes a comma or newline separated list. Empty values count as unset.
+    pub fn apply_env_overrides(&mut self) -> Result<(), ConfigReadError> {
+        if let Some(token) = read_env_secret("RNS_MAIN_TOKEN")? {
+            self.main_token = SecretToken::new(token);
+        }
+        if let Some(tokens) = read_env_secret("RNS_SUB_TOKENS")? {
+            self.sub_tokens = tokens
+                .split(|c| c == ',' || c == '\n')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(SecretToken::new)
+                .collect();
+        }
+        if let Some(webhook) = read_env_secret("RNS_WEBHOOK")? {
+            self.webhook = webhook;
+        }
+        Ok(())
+    }
+
+    /// Checks the config for every problem we can spot before connecting to Discord.
+    pub fn validate(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+
+        for key in &self.unknown_keys {
+            problems.push(ConfigProblem::new(
+                format!("$.{}", key),
+                "This key isn't a config option",
+                "Remove it or check its spelling.",
+            ));
+        }
+
+        if !is_token_shaped(self.main_token.expose()) {
+            problems.push(ConfigProblem::new(
+                "$.main_token",
+                "This doesn't look like a Discord token",
+                "Paste the token of the account that should receive the Nitro.",
+            ));
+        }
+
+        let mut seen_tokens = Vec::new();
+        for (index, token) in self.sub_tokens.iter().enumerate() {
+            let path = format!("$.sub_tokens[{}]", index);
+            if !is_token_shaped(token.expose()) {
+                problems.push(ConfigProblem::new(
+                    path,
+                    "This doesn't look like a Discord token",
+                    "Paste a valid token or remove the entry.",
+                ));
+            } else if *token == self.main_token {
+                problems.push(ConfigProblem::new(
+                    path,
+                    "This is your main token",
+                    "Remove it and use snipe_on_main_token instead.",
+                ));
+            } else if seen_tokens.contains(&token) {
+                problems.push(ConfigProblem::new(
+                    path,
+                    "This token is listed more than once",
+                    "Remove the duplicate entry.",
+                ));
+            }
+            seen_tokens.push(token);
+        }
+
+        if !self.snipe_on_main_token && self.sub_tokens.is_empty() {
+            problems.push(ConfigProblem::new(
+                "$.sub_tokens",
+                "There's no token to snipe with",
+                "Add a sub-token or set snipe_on_main_token to true.",
+            ));
+        }
+
+        if !self.webhook.is_empty() && !is_webhook_shaped(&self.webhook) {
+            problems.push(ConfigProblem::new(
+                "$.webhook",
+                "This isn't a Discord webhook URL",
+                "Use a URL like https://discord.com/api/webhooks/<id>/<token> or leave it empty.",
+            ));
+        }
+
+        let mut seen_guilds = Vec::new();
+        for (index, id) in self.guild_blacklist.iter().enumerate() {
+            let path = format!("$.guild_blacklist[{}]", index);
+            if *id < MIN_SNOWFLAKE {
+                problems.push(ConfigProblem::new(
+                    path,
+                    "This is too small to be a guild ID",
+                    "Copy the ID with Discord's developer mode (right click the guild > Copy ID).",
+                ));
+            } else if seen_guilds.contains(&id) {
+                problems.push(ConfigProblem::new(
+                    path,
+                    "This guild is listed more than once",
+                    "Remove the duplicate entry.",
+                ));
+            }
+            seen_guilds.push(id);
+        }
+
+        if let Some(patterns) = &self.gift_patterns {
+            if patterns.is_empty() {
+                problems.push(ConfigProblem::new(
+                    "$.gift_patterns",
+                    "There's no pattern to find gift codes with",
+                    "Add a pattern or remove the key to use the built-in ones.",
+                ));
+            }
+            for (index, pattern) in patterns.iter().enumerate() {
+                if let Err(reason) = pattern.check() {
+                    problems.push(ConfigProblem::new(
+                        format!("$.gift_patterns[{}]", index),
+                        reason,
+                        "Fix the regex so the code is in the capture group given by group.",
+                    ));
+                }
+            }
+        }
+
+        if self.seen_codes_capacity == 0 {
+            problems.push(ConfigProblem::new(
+                "$.seen_codes_capacity",
+                "Every code would be forgotten as soon as it's seen",
+                "Set it to 1 or more, or remove the key to use the default of 100000.",
+            ));
+        }
+        if self
+            .seen_codes_ttl_hours
+            .checked_mul(SECONDS_PER_HOUR)
+            .is_none()
+        {
+            problems.push(ConfigProblem::new(
+                "$.seen_codes_ttl_hours",
+                format!(
+                    "{} hours is too long to keep track of",
+                    self.seen_codes_ttl_hours
+                ),
+                "Use a smaller number of hours, or remove the key to use the default of 72.",
+            ));
+        }
+
+        if self.redeem_concurrency == 0 {
+            problems.push(ConfigProblem::new(
+                "$.redeem_concurrency",
+                "No code could ever be redeemed with a concurrency of 0",
+                "Set it to 1 or more, or remove the key to use the default of 4.",
+            ));
+        }
+        if self.redeem_timeout_ms == 0 {
+            problems.push(ConfigProblem::new(
+                "$.redeem_timeout_ms",
+                "Every redeem attempt would time out right away",
+                "Set it to a number of milliseconds, or remove the key to use the default.",
+            ));
+        }
+
+        if let Some(threshold) = self.score_threshold {
+            if !(0_f64..=1_f64).contains(&threshold) {
+                problems.push(ConfigProblem::new(
+                    "$.score_threshold",
+                    format!("{} is not between 0 and 1", threshold),
+                    "Pick a threshold from 0 to 1, or remove the key to use the scorer's default.",
+                ));
+            }
+        }
+
+        if let Err(reason) = self.check_api_base() {
+            problems.push(ConfigProblem::new(
+                "$.api_base",
+                reason,
+                "Use an https:// URL, or remove the key to use Discord's API.",
+            ));
+        }
+
+        problems
+    }
+
+    fn check_api_base(&self) -> Result<(), String> {
+        let uri = self
+            .api_base
+            .parse::<Uri>()
+            .map_err(|_| format!("\"{}\" is not a valid API base URL", self.api_base))?;
+
+        match (uri.scheme_str(), uri.host()) {
+            (Some("https"), Some(_)) => Ok(()),
+            (Some("http"), Some(host)) if is_loopback(host) => Ok(()),
+            (Some("http"), Some(_)) => Err(
+                "Plain HTTP is only allowed for a loopback API base URL (localhost, 127.0.0.1, [::1])"
+                    .to_string(),
+            ),
+            _ => Err(format!("\"{}\" is not a valid API base URL", self.api_base)),
+        }
+    }
+
+    /// The matcher built from `gift_patterns` (or the built-in patterns) and the configured scorer.
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    /// The configured `gift_patterns`, or the built-in patterns if there are none.
+    pub fn gift_patterns(&self) -> Vec<GiftPattern> {
+        self.gift_patterns
+            .clone()
+            .unwrap_or_else(matcher::builtin_patterns)
+    }
+
+    pub fn scorer(&self) -> ScorerKind {
+        self.scorer
+    }
+
+    /// The configured `score_threshold`, or the scorer's default if there is none.
+    pub fn score_threshold(&self) -> f64 {
+        self.score_threshold
+            .unwrap_or_else(|| self.scorer.default_threshold())
+    }
+
     pub fn is_guild_blacklisted(&self, id: Option<GuildId>) -> bool {
         id.map_or_else(|| false, |i| self.guild_blacklist.contains(i.as_u64()))
     }
 }
 
+/// A single problem found while validating the config.
+#[derive(Debug, Clone)]
+pub struct ConfigProblem {
+    pub path: String,
+    pub problem: String,
+    pub fix: String,
+}
+
+impl ConfigProblem {
+    fn new(path: impl Into<String>, problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        ConfigProblem {
+            path: path.into(),
+            problem: problem.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}. {}", self.path, self.problem, self.fix)
+    }
+}
+
 pub enum ConfigReadError {
-    NoSuchFile,
+    NoSuchFile(PathBuf),
     FailedReading,
     MalformedConfig(String),
+    InvalidConfig(Vec<ConfigProblem>),
+    FailedReadingSecret(String, PathBuf),
+    Vault(VaultError),
 }
 
 impl ConfigReadError {
     pub fn handle(&self) {
         match self {
-            ConfigReadError::NoSuchFile => match create_config() {
+            ConfigReadError::NoSuchFile(path) => match create_config(path) {
                 Ok(_) => {
                     log_error_and_exit!(
-                            "┐(¯ω¯;)┌",
-                            "No previous config file found. Please change your configuration in the rns-config.json file I just created!"
-                        );
+                        code: ExitCode::ConfigMissing,
+                        "┐(¯ω¯;)┌",
+                        "No previous config file found. Please change your configuration in the {} file I just created!",
+                        path.display()
+                    );
                 }
                 Err(_) => {
                     log_error_and_exit!(
-                            "┐(¯ω¯;)┌",
-                            "No previous config file found. Please create an rns-config.json file with your configuration!"
-                        );
+                        code: ExitCode::ConfigMissing,
+                        "┐(¯ω¯;)┌",
+                        "No previous config file found. Please create an {} file with your configuration!",
+                        path.display()
+                    );
                 }
             },
             ConfigReadError::MalformedConfig(reason) => {
@@ -79,8 +553,33 @@ impl ConfigReadError {
                     "┐(¯ω¯;)┌",
                     "I couldn't read you config. Did you format it correctly?"
                 );
-                log_error_and_exit!("->", "...{}.", reason);
+                log_error_and_exit!(code: ExitCode::ConfigMalformed, "->", "...{}.", reason);
+            }
+            ConfigReadError::InvalidConfig(problems) => {
+                pretty_error!(
+                    "┐(¯ω¯;)┌",
+                    "I found {} problem(s) in your config:",
+                    problems.len()
+                );
+                for problem in problems {
+                    pretty_error!("->", "{}", problem);
+                }
+                log_error_and_exit!(
+                    code: ExitCode::ConfigMalformed,
+                    "(╥ω╥)",
+                    "Please fix them and try again!"
+                );
             }
+            ConfigReadError::FailedReadingSecret(variable, path) => {
+                log_error_and_exit!(
+                    code: ExitCode::ConfigMissing,
+                    "┐(¯ω¯;)┌",
+                    "I wasn't able to read the secrets file {} from {}...",
+                    path.display(),
+                    variable
+                );
+            }
+            ConfigReadError::Vault(error) => error.handle(),
             ConfigReadError::FailedReading => {
                 log_error_and_exit!("┐(¯ω¯;)┌", "I wasn't able to open your config...");
             }
@@ -88,28 +587,194 @@ impl ConfigReadError {
     }
 }
 
-enum ConfigWriteError {
+impl fmt::Display for ConfigReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigReadError::NoSuchFile(path) => write!(f, "{} doesn't exist", path.display()),
+            ConfigReadError::FailedReading => write!(f, "the file couldn't be opened"),
+            ConfigReadError::MalformedConfig(reason) => write!(f, "{}", reason),
+            ConfigReadError::InvalidConfig(problems) => {
+                let problems = problems
+                    .iter()
+                    .map(ConfigProblem::to_string)
+                    .collect::<Vec<_>>();
+                write!(f, "{}", problems.join(" "))
+            }
+            ConfigReadError::FailedReadingSecret(variable, path) => write!(
+                f,
+                "the secrets file {} from {} couldn't be read",
+                path.display(),
+                variable
+            ),
+            ConfigReadError::Vault(_) => write!(f, "the vault couldn't be unlocked"),
+        }
+    }
+}
+
+pub enum ConfigWriteError {
+    AlreadyExists,
     FailedCreating,
     FailedWriting,
 }
 
-pub fn try_read_config() -> Result<Config, ConfigReadError> {
-    let mut file = File::open("rns-config.json").map_err(|_| ConfigReadError::NoSuchFile)?;
+impl ConfigWriteError {
+    pub fn handle(&self) {
+        match self {
+            ConfigWriteError::AlreadyExists => {
+                log_error_and_exit!(
+                    "┐(¯ω¯;)┌",
+                    "There already is a config file there. Use --force to overwrite it."
+                );
+            }
+            ConfigWriteError::FailedCreating => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "I wasn't able to create your config...");
+            }
+            ConfigWriteError::FailedWriting => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "I wasn't able to write your config...");
+            }
+        }
+    }
+}
+
+/// Reads and parses the config file at the given path.
+pub fn try_read_config(path: &Path) -> Result<Config, ConfigReadError> {
+    let mut file = File::open(path).map_err(|_| ConfigReadError::NoSuchFile(path.to_path_buf()))?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)
         .map_err(|_| ConfigReadError::FailedReading)?;
-    serde_json::from_str::<Config>(&contents)
-        .map_err(|e| ConfigReadError::MalformedConfig(e.to_string()))
-}
-
-fn create_config() -> Result<(), ConfigWriteError> {
-    let default_config = Config::default();
-    let mut file = File::create("rns-config.json").map_err(|_| ConfigWriteError::FailedCreating)?;
-    file.write_all(
-        serde_json::to_string_pretty(&default_config)
-            .unwrap()
-            .as_bytes(),
-    )
-    .map_err(|_| ConfigWriteError::FailedWriting)?;
+    let raw = ConfigFormat::from_path(path)
+        .parse(&contents)
+        .map_err(ConfigReadError::MalformedConfig)?;
+    let mut config = serde_json::from_value::<Config>(raw.clone())
+        .map_err(|e| ConfigReadError::MalformedConfig(e.to_string()))?;
+    config.unknown_keys = find_unknown_keys(&raw);
+    config.apply_vault()?;
+    config.apply_env_overrides()?;
+
+    let problems = config.validate();
+    if !problems.is_empty() {
+        return Err(ConfigReadError::InvalidConfig(problems));
+    }
+
+    config.matcher = Matcher::new(config.gift_patterns())
+        .map_err(ConfigReadError::MalformedConfig)?
+        .with_scorer(config.scorer.build(), config.score_threshold());
+    Ok(config)
+}
+
+/// Reads a secret from `name` or the file `name_FILE` points at. Empty values count as unset.
+fn read_env_secret(name: &str) -> Result<Option<String>, ConfigReadError> {
+    if let Some(value) = env::var(name).ok().and_then(non_empty) {
+        return Ok(Some(value));
+    }
+
+    let file_variable = format!("{}_FILE", name);
+    match env::var_os(&file_variable).filter(|p| !p.is_empty()) {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            let value = fs::read_to_string(&path)
+                .map_err(|_| ConfigReadError::FailedReadingSecret(file_variable, path))?;
+            Ok(non_empty(value))
+        }
+        None => Ok(None),
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn find_unknown_keys(raw: &Value) -> Vec<String> {
+    let known = serde_json::to_value(Config::default()).unwrap();
+    match (raw.as_object(), known.as_object()) {
+        (Some(raw), Some(known)) => raw
+            .keys()
+            .filter(|k| !known.contains_key(*k))
+            .cloned()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_token_shaped(token: &str) -> bool {
+    lazy_static! {
+        static ref TOKEN_PATTERN: Regex =
+            Regex::new(r"^(mfa\.[\w-]{20,}|[\w-]{20,}\.[\w-]{6,}\.[\w-]{20,})$").unwrap();
+    }
+    TOKEN_PATTERN.is_match(token)
+}
+
+fn is_webhook_shaped(url: &str) -> bool {
+    lazy_static! {
+        static ref WEBHOOK_PATTERN: Regex = Regex::new(
+            r"^https://(ptb\.|canary\.)?(discord|discordapp)\.com/api(/v\d+)?/webhooks/\d+/[\w-]+/?$"
+        )
+        .unwrap();
+    }
+    WEBHOOK_PATTERN.is_match(url)
+}
+
+/// Writes the default config to the given path, refusing to overwrite unless `force` is set.
+pub fn write_default_config(path: &Path, force: bool) -> Result<(), ConfigWriteError> {
+    if path.exists() && !force {
+        return Err(ConfigWriteError::AlreadyExists);
+    }
+    create_config(path)
+}
+
+fn is_loopback(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host == "localhost"
+        || host
+            .parse::<std::net::IpAddr>()
+            .map_or(false, |ip| ip.is_loopback())
+}
+
+fn create_config(path: &Path) -> Result<(), ConfigWriteError> {
+    let template = ConfigFormat::from_path(path).template();
+    let mut file = File::create(path).map_err(|_| ConfigWriteError::FailedCreating)?;
+    file.write_all(template.as_bytes())
+        .map_err(|_| ConfigWriteError::FailedWriting)?;
     Ok(())
 }
+
+/// The file formats a config can be written in, picked by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    /// Parses a config in this format into JSON, without checking any of its keys.
+    pub fn parse(self, contents: &str) -> Result<Value, String> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// The default config in this format, with comments where the format allows them.
+    pub fn template(self) -> String {
+        match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(&Config::default()).unwrap(),
+            ConfigFormat::Toml => include_str!("templates/rns-config.toml").to_string(),
+            ConfigFormat::Yaml => include_str!("templates/rns-config.yaml").to_string(),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::config::Config;
+use crate::discord::{HandlerInfo, HttpsClient};
+use crate::{pretty_info, pretty_warn};
+use colored::*;
+use futures::future::join_all;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request};
+use hyper_tls::HttpsConnector;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long an unused connection stays in the pool. Longer than the keep-alive interval.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// How many idle connections to keep per host.
+const POOL_MAX_IDLE: usize = 16;
+/// TCP keep-alive, so dead connections are noticed by the OS.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// Builds the HTTPS client with connection reuse, speaking only HTTP/2 or only HTTP/1.1.
+///
+/// hyper can't tell which one the TLS handshake settled on, so an HTTP/2 client only offers
+/// HTTP/2 and fails against servers that don't speak it. Use `connect_client` to fall back.
+pub fn build_client(http2: bool) -> HttpsClient {
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_nodelay(true);
+    http.set_keepalive(Some(TCP_KEEPALIVE));
+
+    let mut tls = native_tls::TlsConnector::builder();
+    if http2 {
+        tls.request_alpns(&["h2"]);
+    }
+    let tls = tls.build().expect("(o_O) Failed setting up TLS. (HOW?)");
+    let https = HttpsConnector::from((http, tokio_tls::TlsConnector::from(tls)));
+
+    Client::builder()
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .pool_max_idle_per_host(POOL_MAX_IDLE)
+        .http2_only(http2)
+        .build::<_, Body>(https)
+}
+
+/// Builds the client for the API in the config, trying HTTP/2 first if `http2` is on.
+///
+/// Falls back to HTTP/1.1 if the API (or a proxy in between) can't be reached over HTTP/2
+/// but can over HTTP/1.1.
+pub async fn connect_client(config: &Config) -> HttpsClient {
+    if !config.http2() {
+        return build_client(false);
+    }
+
+    let client = build_client(true);
+    if ping(&client, config).await {
+        return client;
+    }
+    let fallback = build_client(false);
+    if !ping(&fallback, config).await {
+        return client;
+    }
+    pretty_warn!(
+        "(・へ・)",
+        "Discord's API didn't answer over HTTP/2, so I'll use HTTP/1.1 instead."
+    );
+    fallback
+}
+
+/// Sends a request that needs no token and costs nothing against the redeem rate limit.
+async fn ping(client: &HttpsClient, config: &Config) -> bool {
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri(config.api_url("/gateway"))
+        .body(Body::empty())
+        .unwrap();
+
+    match client.request(request).await {
+        Ok(response) => hyper::body::to_bytes(response.into_body()).await.is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Opens `amount` connections to the API at once, so the first redeems don't pay for the
+/// DNS lookup and TLS handshake.
+pub async fn prewarm(client: &HttpsClient, config: &Config, amount: usize) {
+    let start = Instant::now();
+    let pings = (0..amount.max(1)).map(|_| ping(client, config));
+    let warmed = join_all(pings).await.into_iter().filter(|ok| *ok).count();
+
+    if warmed == 0 {
+        pretty_warn!(
+            "┐(¯ω¯;)┌",
+            "I couldn't reach Discord's API ahead of time. The first redeem might be slow."
+        );
+    } else {
+        pretty_info!(
+            "(o·ω·o)",
+            "Warmed up {} connection(s) to Discord in {}ms.",
+            warmed,
+            start.elapsed().as_millis()
+        );
+    }
+}
+
+/// Pings the API every `interval` so pooled connections don't go idle and get closed.
+pub async fn keep_alive(info: Arc<HandlerInfo>, interval: Duration, amount: usize) {
+    let mut ticks = tokio::time::interval(interval);
+    // The first tick fires right away, right after `prewarm`.
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let config = info.config();
+        let pings = (0..amount.max(1)).map(|_| ping(info.client(), &config));
+        join_all(pings).await;
+    }
+}
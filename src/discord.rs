@@ -1,143 +1,236 @@
 use crate::cache::LocationCache;
 use crate::config::Config;
-use crate::logging::LogBlock;
-use crate::matcher::get_gift_code;
-use crate::util::user_to_tag;
-use crate::webhook::Webhook;
-use crate::{log_error_and_exit, pretty_error, pretty_info, pretty_success, pretty_warn};
+use crate::gift::GiftInfo;
+use crate::logging::{ExitCode, LogBlock, Timeline};
+use crate::matcher::GiftMatch;
+use crate::redeem::{rate_limit_wait, retry_after_from_header, RedeemOutcome};
+use crate::stats::{ClaimTimings, StatsFile};
+use crate::store::{SeenCode, SeenCodeStore};
+use crate::token::SecretToken;
+use crate::util::RecentlySeen;
+use crate::worker::{FetchJob, Finder, RedeemJob, RedeemQueue};
+use crate::{log_error_and_exit, pretty_info, pretty_warn};
 use colored::*;
 use hyper::client::HttpConnector;
+use hyper::header::HeaderMap;
 use hyper::{Body, Client, Method, Request, StatusCode};
 use hyper_tls::HttpsConnector;
 use once_cell::sync::OnceCell;
 use serenity::async_trait;
 use serenity::http::{CacheHttp, GuildPagination, Http};
 use serenity::model::channel::Message;
+use serenity::model::event::MessageUpdateEvent;
 use serenity::model::gateway::Ready;
-use serenity::model::id::GuildId;
+use serenity::model::id::{GuildId, MessageId};
 use serenity::model::user::CurrentUser;
 use serenity::prelude::{Context, EventHandler};
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex, RwLock};
+use std::task::Poll;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
+use tokio::time::{delay_for, timeout, timeout_at};
 
-type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+/// Discord's epoch, the first millisecond of 2015, in Unix milliseconds.
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+/// How many replied-to messages to remember as already scanned.
+const SCANNED_REFERENCES_CAPACITY: usize = 1_000;
+/// How many rejected codes to remember, so each one is only logged once.
+const REJECTED_CODES_CAPACITY: usize = 10_000;
 
+/// How long to back off after a 429 that didn't say for how long.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+/// How many times to try redeeming a code that keeps getting rate-limited.
+const MAX_REDEEM_ATTEMPTS: usize = 3;
+
+/// The HTTPS client shared by every request RNS makes.
+pub type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+
+/// State shared between the event handlers of all sniping accounts.
 pub struct HandlerInfo {
     client: HttpsClient,
-    config: Config,
-    seen_codes: Mutex<Vec<String>>,
-    token_amount: usize,
-    connected: AtomicUsize,
-    total_guilds: AtomicUsize,
+    config: RwLock<Arc<Config>>,
+    seen_codes: Mutex<Box<dyn SeenCodeStore>>,
+    stats: Option<StatsFile>,
+    redeem_queue: RedeemQueue,
+    token_amount: AtomicUsize,
+    /// How many guilds each connected account is in.
+    connected: StdMutex<HashMap<SecretToken, usize>>,
+    /// Replied-to messages some account already fetched, newest last.
+    scanned_references: StdMutex<RecentlySeen<MessageId>>,
+    /// Codes whose score was too low, which we've already logged.
+    rejected_codes: StdMutex<RecentlySeen<String>>,
 }
 
 impl HandlerInfo {
-    pub fn new(client: HttpsClient, config: Config, token_amount: usize) -> Self {
+    pub fn new(
+        client: HttpsClient,
+        config: Config,
+        seen_codes: Box<dyn SeenCodeStore>,
+        redeem_queue: RedeemQueue,
+        token_amount: usize,
+    ) -> Self {
         HandlerInfo {
             client,
-            config,
-            seen_codes: Mutex::new(Vec::new()),
-            token_amount,
-            connected: AtomicUsize::new(0),
-            total_guilds: AtomicUsize::new(0),
+            config: RwLock::new(Arc::new(config)),
+            seen_codes: Mutex::new(seen_codes),
+            stats: None,
+            redeem_queue,
+            token_amount: AtomicUsize::new(token_amount),
+            connected: StdMutex::new(HashMap::new()),
+            scanned_references: StdMutex::new(RecentlySeen::new(SCANNED_REFERENCES_CAPACITY)),
+            rejected_codes: StdMutex::new(RecentlySeen::new(REJECTED_CODES_CAPACITY)),
+        }
+    }
+
+    /// Also writes the timings of every claim attempt to `stats`.
+    pub fn with_stats(mut self, stats: Option<StatsFile>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// A snapshot of the current config. Reloads never change a snapshot that's in use.
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Swaps in a reloaded config for every handler at once.
+    pub fn replace_config(&self, config: Config, token_amount: usize) {
+        *self.config.write().unwrap() = Arc::new(config);
+        self.token_amount.store(token_amount, Ordering::Relaxed);
+    }
+
+    pub fn client(&self) -> &HttpsClient {
+        &self.client
+    }
+
+    pub async fn record_outcome(&self, code: &str, outcome: &str) {
+        self.seen_codes.lock().await.record_outcome(code, outcome);
+    }
+
+    /// Forgets a code that was never really tried, so it's tried again if it shows up again.
+    pub async fn forget_code(&self, code: &str) {
+        self.seen_codes.lock().await.forget(code);
+    }
+
+    pub fn record_timings(&self, timings: &ClaimTimings) {
+        if let Some(stats) = &self.stats {
+            stats.record(timings);
+        }
+    }
+
+    pub async fn record_gift_info(&self, code: &str, gift: &GiftInfo) {
+        self.seen_codes.lock().await.record_gift_info(code, gift);
+    }
+
+    pub async fn compact_seen_codes(&self) {
+        self.seen_codes.lock().await.compact();
+    }
+
+    /// Counts the account as connected, returning how many accounts and guilds there are now.
+    pub fn account_connected(&self, token: &SecretToken, guild_amount: usize) -> (usize, usize) {
+        let mut connected = self.connected.lock().unwrap();
+        connected.insert(token.clone(), guild_amount);
+        (connected.len(), connected.values().sum())
+    }
+
+    pub fn account_disconnected(&self, token: &SecretToken) {
+        self.connected.lock().unwrap().remove(token);
+    }
+
+    /// Whether no account has fetched this replied-to message yet, claiming it if so.
+    fn claim_reference(&self, message_id: MessageId) -> bool {
+        self.scanned_references.lock().unwrap().insert(message_id)
+    }
+
+    /// Marks each accepted code as seen and queues it for redeeming, without waiting on any request.
+    pub async fn snipe_all(
+        &self,
+        msg: &Message,
+        gift_matches: Vec<GiftMatch>,
+        config: &Arc<Config>,
+        finder: &Finder,
+        timeline: Timeline,
+    ) {
+        for gift_match in gift_matches {
+            if !config.matcher().accepts(&gift_match) {
+                self.log_rejected(&[gift_match], config);
+                continue;
+            }
+
+            let mut timeline = timeline.clone();
+            {
+                let mut seen_codes = self.seen_codes.lock().await;
+                if seen_codes.contains(&gift_match.code) {
+                    continue;
+                }
+                seen_codes.insert(SeenCode::new(
+                    gift_match.code.clone(),
+                    finder.profile.to_string(),
+                ));
+            }
+            timeline.mark("dedup");
+
+            self.redeem_queue.submit(RedeemJob {
+                gift_match,
+                message: msg.clone(),
+                finder: finder.clone(),
+                config: config.clone(),
+                timeline,
+            });
         }
     }
+
+    /// Logs codes whose score was too low, but each one only once.
+    fn log_rejected(&self, gift_matches: &[GiftMatch], config: &Config) {
+        let mut rejected_codes = self.rejected_codes.lock().unwrap();
+        for gift_match in gift_matches {
+            if !rejected_codes.insert(gift_match.code.clone()) {
+                continue;
+            }
+            pretty_info!(
+                "(・へ・)",
+                "Ignoring {} (matched {}): score {:.3} is below {}.",
+                gift_match.code,
+                gift_match.pattern,
+                gift_match.score,
+                config.matcher().threshold()
+            );
+        }
+    }
+
+    /// How many accounts are connected and how many guilds they're in together.
+    pub fn connected(&self) -> (usize, usize) {
+        let connected = self.connected.lock().unwrap();
+        (connected.len(), connected.values().sum())
+    }
 }
 
+/// Serenity event handler for a single sniping account.
 pub struct Handler {
+    token: SecretToken,
+    /// When the handler was made, in Unix milliseconds. Later messages reach us as events.
+    created_at: i64,
     initialized: AtomicBool,
     profile: OnceCell<Profile>,
-    location_cache: LocationCache,
+    location_cache: Arc<LocationCache>,
     info: Arc<HandlerInfo>,
 }
 
 impl Handler {
-    pub fn new(info: Arc<HandlerInfo>) -> Self {
+    pub fn new(info: Arc<HandlerInfo>, token: SecretToken) -> Self {
         Handler {
+            token,
+            created_at: chrono::Utc::now().timestamp_millis(),
             initialized: AtomicBool::new(false),
             profile: OnceCell::new(),
-            location_cache: LocationCache::new(),
+            location_cache: Arc::new(LocationCache::new()),
             info,
         }
     }
 
-    async fn make_request(&self, gift_code: String, message: &Message, log: &mut LogBlock<'_>) {
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri(format!(
-                "https://discordapp.com/api/v8/entitlements/gift-codes/{}/redeem",
-                gift_code
-            ))
-            .header("Authorization", &self.info.config.main_token())
-            .header("Content-Length", 0)
-            .body(Body::empty())
-            .unwrap();
-
-        if let Ok(response) = self.info.client.request(request).await {
-            match response.status() {
-                StatusCode::OK => self.on_success(message, log).await,
-                StatusCode::METHOD_NOT_ALLOWED => {
-                    pretty_error!(log: log, "(x_x)", "There was an error on Discord's side.");
-                }
-                StatusCode::NOT_FOUND => {
-                    pretty_warn!(log: log, "(╥ω╥)", "Code was fake or expired.");
-                }
-                StatusCode::BAD_REQUEST => {
-                    pretty_error!(log: log, "(╥ω╥)", "Code was already redeemed.");
-                }
-                StatusCode::TOO_MANY_REQUESTS => {
-                    pretty_warn!(log: log, "(x_x)", "We were rate-limited...");
-                }
-                unknown => {
-                    pretty_error!(
-                        log: log,
-                        "┐(¯ω¯;)┌",
-                        "Received unknown response... ({}{})",
-                        unknown.as_str(),
-                        unknown
-                            .canonical_reason()
-                            .map_or_else(|| "".to_string(), |r| format!(" {}", r))
-                    );
-                    if let Ok(Ok(body)) = hyper::body::to_bytes(response.into_body())
-                        .await
-                        .map(|b| String::from_utf8(b.to_vec()))
-                    {
-                        pretty_error!(log: log, "->", "...with this body: {}", body);
-                    } else {
-                        pretty_error!(
-                            log: log,
-                            "->",
-                            "...and couldn't parse the body of the response."
-                        );
-                    }
-                }
-            }
-        } else {
-            pretty_warn!(
-                log: log,
-                "┐(¯ω¯;)┌",
-                "Requesting failed. Check your connection!"
-            );
-        }
-    }
-
-    async fn on_success(&self, message: &Message, log: &mut LogBlock<'_>) {
-        pretty_success!(log: log, "o(»ω«)o", "Yay! Claimed code!");
-        if let Some(webhook_url) = self.info.config.webhook() {
-            pretty_success!(log: log, "(o·ω·o)", "Sending webhook message!");
-            let webhook = Webhook::new(webhook_url);
-            if webhook
-                .send(message, &self.info.client, self.profile.get().unwrap())
-                .await
-                .is_err()
-            {
-                pretty_error!(log: log, "┐(¯ω¯;)┌", "Failed sending webhook message");
-            }
-        }
-    }
-
     fn initialize(&self, profile: Profile, guild_amount: usize) {
         pretty_info!(
             "(o·ω·o)",
@@ -146,22 +239,56 @@ impl Handler {
             guild_amount.to_string().as_str().magenta().bold()
         );
         self.profile.set(profile).unwrap();
-        self.info
-            .total_guilds
-            .fetch_add(guild_amount, Ordering::Relaxed);
 
-        if self.info.connected.fetch_add(1, Ordering::Relaxed) + 1 == self.info.token_amount
-            && self.info.token_amount > 1
-        {
+        let (connected, total_guilds) = self.info.account_connected(&self.token, guild_amount);
+        let token_amount = self.info.token_amount.load(Ordering::Relaxed);
+        if connected == token_amount && token_amount > 1 {
             pretty_info!(
                 "( ´-ω·)±┻┳══━─",
                 "Connected to all {} accounts! Sniping in {} guilds in total!",
-                self.info.token_amount,
-                self.info.total_guilds.load(Ordering::Relaxed)
+                token_amount,
+                total_guilds
             );
         }
     }
 
+    fn finder(&self, ctx: &Context) -> Finder {
+        Finder {
+            profile: self.profile.get().unwrap().clone(),
+            http: ctx.http.clone(),
+            location_cache: self.location_cache.clone(),
+        }
+    }
+
+    /// Has a worker scan the message a reply points at, unless it was sent while we were
+    /// connected, in which case we already saw it.
+    fn snipe_referenced(&self, ctx: &Context, msg: &Message, config: &Arc<Config>) {
+        let reference = match &msg.message_reference {
+            Some(reference) => reference,
+            None => return,
+        };
+        let message_id = match reference.message_id {
+            Some(message_id) => message_id,
+            None => return,
+        };
+        let sent_at = (message_id.0 >> 22) as i64 + DISCORD_EPOCH_MS;
+        if sent_at >= self.created_at
+            || config.is_guild_blacklisted(reference.guild_id)
+            || !self.info.claim_reference(message_id)
+        {
+            return;
+        }
+
+        self.info.redeem_queue.fetch(FetchJob {
+            channel_id: reference.channel_id,
+            message_id,
+            gift_matches: None,
+            finder: self.finder(ctx),
+            config: config.clone(),
+            timeline: Timeline::new(),
+        });
+    }
+
     async fn initialize_from_raw(&self, http: &Http) {
         self.initialized.store(true, Ordering::Relaxed);
         let profile = Profile::from(http.get_current_user().await.unwrap());
@@ -183,25 +310,65 @@ impl EventHandler for Handler {
             return;
         }
 
-        if !self.info.config.is_guild_blacklisted(msg.guild_id) {
-            if let Some(gift_code) = get_gift_code(&msg) {
-                let mut seen_codes = self.info.seen_codes.lock().await;
-                if !seen_codes.contains(&gift_code) {
-                    seen_codes.push(gift_code.clone());
+        let mut timeline = Timeline::new();
+        let config = self.info.config();
+        if !config.is_guild_blacklisted(msg.guild_id) {
+            let gift_matches = config.matcher().score_message(&msg);
+            timeline.mark("detect");
+            if !gift_matches.is_empty() {
+                self.info
+                    .snipe_all(&msg, gift_matches, &config, &self.finder(&ctx), timeline)
+                    .await;
+            }
+            self.snipe_referenced(&ctx, &msg, &config);
+        }
+    }
 
-                    let mut log = LogBlock::new(self.profile.get().unwrap());
-                    pretty_info!(log: log, "(°■°)!", "Claiming code: {}!", gift_code);
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        if self.profile.get().is_none() {
+            return;
+        }
 
-                    self.make_request(gift_code, &msg, &mut log).await;
-                    log.freeze_time();
+        let mut timeline = Timeline::new();
+        let config = self.info.config();
+        if config.is_guild_blacklisted(event.guild_id) {
+            return;
+        }
 
-                    let location = self
-                        .location_cache
-                        .get_and_cache_location(msg.channel_id, msg.guild_id, ctx.http())
-                        .await;
-                    log.send(location, user_to_tag(&msg.author));
-                }
+        // Discord also sends edits when link previews load, so skip anything we've handled.
+        let mut gift_matches = config.matcher().score_edit(&event);
+        timeline.mark("detect");
+        {
+            let seen_codes = self.info.seen_codes.lock().await;
+            gift_matches.retain(|m| !seen_codes.contains(&m.code));
+        }
+        timeline.mark("seen check");
+        if !gift_matches.iter().any(|m| config.matcher().accepts(m)) {
+            self.info.log_rejected(&gift_matches, &config);
+            return;
+        }
+
+        match new {
+            Some(msg) => {
+                self.info
+                    .snipe_all(&msg, gift_matches, &config, &self.finder(&ctx), timeline)
+                    .await
             }
+            // The edit doesn't carry the author or where it was sent, so a worker fetches it.
+            None => self.info.redeem_queue.fetch(FetchJob {
+                channel_id: event.channel_id,
+                message_id: event.id,
+                gift_matches: Some(gift_matches),
+                finder: self.finder(&ctx),
+                config,
+                timeline,
+            }),
         }
     }
 
@@ -252,59 +419,393 @@ impl fmt::Display for Profile {
     }
 }
 
+/// The Discord API routes RNS calls, each with its own rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Route {
+    Redeem,
+    Profile,
+    GiftInfo,
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Route::Redeem => write!(f, "redeem"),
+            Route::Profile => write!(f, "profile"),
+            Route::GiftInfo => write!(f, "gift info"),
+        }
+    }
+}
+
+/// What's left of a rate limit window, as last reported by Discord.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitBudget {
+    pub limit: Option<u64>,
+    pub remaining: u64,
+    pub resets_in: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    reset_at: Option<Instant>,
+}
+
+impl Bucket {
+    fn blocked_until(&self, now: Instant) -> Option<Instant> {
+        match (self.remaining, self.reset_at) {
+            (Some(0), Some(reset_at)) if reset_at > now => Some(reset_at),
+            _ => None,
+        }
+    }
+}
+
+/// Tracks Discord's rate limits per token and route from the `X-RateLimit-*` headers.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: StdMutex<HashMap<(SecretToken, Route), Bucket>>,
+    /// Discord's global limit is per token too, so one account hitting it doesn't block another.
+    global_until: StdMutex<HashMap<SecretToken, Instant>>,
+}
+
+impl RateLimiter {
+    /// Waits until a request on this route is allowed, then counts it against the budget.
+    pub async fn acquire(&self, token: &SecretToken, route: Route) {
+        loop {
+            let now = Instant::now();
+            let blocked_until = {
+                let global_until = self.global_until.lock().unwrap().get(token).copied();
+                let mut buckets = self.buckets.lock().unwrap();
+                let bucket = buckets.entry((token.clone(), route)).or_default();
+                match (global_until.filter(|g| *g > now), bucket.blocked_until(now)) {
+                    (None, None) => {
+                        if bucket.reset_at.map_or(false, |r| r <= now) {
+                            bucket.remaining = bucket.limit;
+                            bucket.reset_at = None;
+                        }
+                        bucket.remaining = bucket.remaining.map(|r| r.saturating_sub(1));
+                        return;
+                    }
+                    (global, local) => global.max(local).unwrap(),
+                }
+            };
+
+            pretty_warn!(
+                "(x_x)",
+                "Waiting {}ms for the {} rate limit of {} to reset...",
+                (blocked_until - now).as_millis(),
+                route,
+                token.label()
+            );
+            delay_for(blocked_until - now).await;
+        }
+    }
+
+    /// Updates the budget from a response. `retry_after` is only set for 429 responses.
+    pub fn update(
+        &self,
+        token: &SecretToken,
+        route: Route,
+        headers: &HeaderMap,
+        retry_after: Option<Duration>,
+    ) {
+        let now = Instant::now();
+        let is_global = headers
+            .get("X-RateLimit-Global")
+            .and_then(|v| v.to_str().ok())
+            .map_or(false, |v| v.eq_ignore_ascii_case("true"));
+        if let (true, Some(retry_after)) = (is_global, retry_after) {
+            self.global_until
+                .lock()
+                .unwrap()
+                .insert(token.clone(), now + retry_after);
+            return;
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry((token.clone(), route)).or_default();
+        if let Some(limit) = header_number(headers, "X-RateLimit-Limit") {
+            bucket.limit = Some(limit as u64);
+        }
+        if let Some(remaining) = header_number(headers, "X-RateLimit-Remaining") {
+            bucket.remaining = Some(remaining as u64);
+        }
+        if let Some(reset_after) =
+            header_number(headers, "X-RateLimit-Reset-After").and_then(rate_limit_wait)
+        {
+            bucket.reset_at = Some(now + reset_after);
+        }
+        if let Some(retry_after) = retry_after {
+            bucket.remaining = Some(0);
+            bucket.reset_at = Some(now + retry_after);
+        }
+    }
+
+    /// The budget left on a route, if Discord has told us about it.
+    pub fn budget(&self, token: &SecretToken, route: Route) -> Option<RateLimitBudget> {
+        let now = Instant::now();
+        let buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get(&(token.clone(), route))?;
+        Some(RateLimitBudget {
+            limit: bucket.limit,
+            remaining: bucket.remaining?,
+            resets_in: bucket
+                .reset_at
+                .map_or(Duration::from_secs(0), |r| r.saturating_duration_since(now)),
+        })
+    }
+}
+
+fn header_number(headers: &HeaderMap, name: &str) -> Option<f64> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| v.is_finite() && *v >= 0_f64)
+}
+
+lazy_static! {
+    /// The rate limits of every token, shared by all requests RNS makes.
+    pub static ref RATE_LIMITS: RateLimiter = RateLimiter::default();
+}
+
+/// A request body that notes when hyper takes it, which is once the request is being written.
+///
+/// hyper never asks for an empty body, so this only works for bodies with content.
+fn timed_body(content: &'static [u8]) -> (Body, Arc<StdMutex<Option<Instant>>>) {
+    let sent = Arc::new(StdMutex::new(None));
+    let taken = sent.clone();
+    let mut content = Some(content);
+    let stream = futures::stream::poll_fn(move |_| {
+        let chunk = content.take();
+        if chunk.is_some() {
+            *taken.lock().unwrap() = Some(Instant::now());
+        }
+        Poll::Ready(chunk.map(Ok::<_, std::io::Error>))
+    });
+    (Body::wrap_stream(stream), sent)
+}
+
+/// Tries redeeming a gift code on the main token from the config.
+///
+/// Waiting for the rate limit doesn't count against `redeem_timeout_ms`, only the request
+/// itself does. The phases of the request are recorded in `timeline`.
+pub async fn redeem_code(
+    gift_code: &str,
+    client: &HttpsClient,
+    config: &Config,
+    timeline: &mut Timeline,
+) -> RedeemOutcome {
+    let (body, sent) = timed_body(b"{}");
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(config.api_url(&format!("/entitlements/gift-codes/{}/redeem", gift_code)))
+        .header("Authorization", config.main_token().expose())
+        .header("Content-Type", "application/json")
+        .header("Content-Length", 2)
+        .body(body)
+        .unwrap();
+
+    let token = config.main_token();
+    RATE_LIMITS.acquire(&token, Route::Redeem).await;
+    timeline.mark("rate limit");
+    let attempt = timeout(config.redeem_timeout(), async {
+        let response = client.request(request).await?;
+        if let Some(sent) = *sent.lock().unwrap() {
+            timeline.mark_at("request sent", sent);
+        }
+        timeline.mark("response headers");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .ok()
+            .and_then(|b| String::from_utf8(b.to_vec()).ok());
+        timeline.mark("body");
+        Ok::<_, hyper::Error>((status, headers, body))
+    });
+
+    match attempt.await {
+        Ok(Ok((status, headers, body))) => {
+            let outcome = RedeemOutcome::from_response(status, &headers, body);
+            let retry_after = match outcome {
+                RedeemOutcome::RateLimited { retry_after } => {
+                    Some(retry_after.unwrap_or(DEFAULT_RETRY_AFTER))
+                }
+                _ => None,
+            };
+            RATE_LIMITS.update(&token, Route::Redeem, &headers, retry_after);
+            outcome
+        }
+        Ok(Err(_)) => RedeemOutcome::TransportError,
+        Err(_) => RedeemOutcome::TimedOut,
+    }
+}
+
+/// Redeems a gift code like `redeem_code`, trying again after being rate-limited.
+///
+/// A code is only tried again while the wait fits in `redeem_timeout_ms`, and at most
+/// `MAX_REDEEM_ATTEMPTS` times. Otherwise the last `RateLimited` outcome is returned.
+pub async fn redeem_code_with_retries(
+    gift_code: &str,
+    client: &HttpsClient,
+    config: &Config,
+    log: &mut LogBlock<'_>,
+) -> RedeemOutcome {
+    let mut outcome = redeem_code(gift_code, client, config, log.timeline()).await;
+    for _ in 1..MAX_REDEEM_ATTEMPTS {
+        let retry_after = match &outcome {
+            RedeemOutcome::RateLimited { retry_after } => {
+                retry_after.unwrap_or(DEFAULT_RETRY_AFTER)
+            }
+            _ => break,
+        };
+        if retry_after > config.redeem_timeout() {
+            break;
+        }
+
+        outcome.log(log);
+        pretty_info!(
+            log: log,
+            "(・へ・)",
+            "Trying again in {}ms...",
+            retry_after.as_millis()
+        );
+        // The rate limiter holds the request back until the wait is over.
+        outcome = redeem_code(gift_code, client, config, log.timeline()).await;
+    }
+    outcome
+}
+
+pub enum GiftInfoError {
+    UnknownCode,
+    RateLimited,
+    Other,
+}
+
+/// Asks Discord what a gift code is for, without redeeming it.
+///
+/// Like `redeem_code`, only the request counts against `redeem_timeout_ms`.
+pub async fn get_gift_info(
+    gift_code: &str,
+    client: &HttpsClient,
+    config: &Config,
+) -> Result<GiftInfo, GiftInfoError> {
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri(config.api_url(&format!(
+            "/entitlements/gift-codes/{}?with_application=false&with_subscription_plan=true",
+            gift_code
+        )))
+        .header("Authorization", config.main_token().expose())
+        .body(Body::empty())
+        .unwrap();
+
+    let token = config.main_token();
+    RATE_LIMITS.acquire(&token, Route::GiftInfo).await;
+    let deadline = Instant::now() + config.redeem_timeout();
+    let response = timeout_at(deadline.into(), client.request(request))
+        .await
+        .map_err(|_| GiftInfoError::Other)?
+        .map_err(|_| GiftInfoError::Other)?;
+
+    let status = response.status();
+    let retry_after = if status == StatusCode::TOO_MANY_REQUESTS {
+        Some(retry_after_from_header(response.headers()).unwrap_or(DEFAULT_RETRY_AFTER))
+    } else {
+        None
+    };
+    RATE_LIMITS.update(&token, Route::GiftInfo, response.headers(), retry_after);
+
+    match status {
+        StatusCode::OK => {
+            let bytes = timeout_at(deadline.into(), hyper::body::to_bytes(response.into_body()))
+                .await
+                .map_err(|_| GiftInfoError::Other)?
+                .map_err(|_| GiftInfoError::Other)?;
+            serde_json::from_slice(&bytes).map_err(|_| GiftInfoError::Other)
+        }
+        StatusCode::NOT_FOUND => Err(GiftInfoError::UnknownCode),
+        StatusCode::TOO_MANY_REQUESTS => Err(GiftInfoError::RateLimited),
+        _ => Err(GiftInfoError::Other),
+    }
+}
+
 pub enum ProfileError {
     Unauthorized,
     RateLimited,
     ConnectionError,
+    Malformed,
     Other,
 }
 
 impl ProfileError {
+    pub fn describe(&self) -> (&'static str, &'static str) {
+        match self {
+            ProfileError::Unauthorized => {
+                ("┐(¯ω¯;)┌", "I couldn't verify this token. Is it correct?")
+            }
+            ProfileError::RateLimited => ("(x_x)", "Your're rate-limited. Try again later..."),
+            ProfileError::ConnectionError => {
+                ("┐(¯ω¯;)┌", "Requesting failed. Check your connection!")
+            }
+            ProfileError::Malformed => ("┐(¯ω¯;)┌", "Discord sent a profile I couldn't read..."),
+            ProfileError::Other => ("┐(¯ω¯;)┌", "Received unknown response for Discord..."),
+        }
+    }
+
     pub fn handle(&self) {
         match self {
             ProfileError::Unauthorized => {
                 log_error_and_exit!(
+                    code: ExitCode::Unauthorized,
                     "┐(¯ω¯;)┌",
                     "I couldn't verify your main token. Is it correct?"
                 );
             }
             ProfileError::RateLimited => {
-                log_error_and_exit!("(x_x)", "Your're rate-limited. Try again later...");
+                let (kaomoji, text) = self.describe();
+                log_error_and_exit!(code: ExitCode::RateLimited, kaomoji, "{}", text);
             }
-            ProfileError::ConnectionError => {
-                log_error_and_exit!("┐(¯ω¯;)┌", "Requesting failed. Check your connection!");
-            }
-            ProfileError::Other => {
-                log_error_and_exit!("┐(¯ω¯;)┌", "Received unknown response for Discord...");
+            _ => {
+                let (kaomoji, text) = self.describe();
+                log_error_and_exit!(kaomoji, "{}", text);
             }
         }
     }
 }
 
+/// Fetches the profile belonging to a token, mostly used to verify it.
 pub async fn get_profile_for_token(
-    token: &str,
+    token: &SecretToken,
     client: &HttpsClient,
+    config: &Config,
 ) -> Result<Profile, ProfileError> {
     let request = Request::builder()
         .method(Method::GET)
-        .uri("https://discordapp.com/api/v6/users/@me")
-        .header("Authorization", token)
+        .uri(config.api_url("/users/@me"))
+        .header("Authorization", token.expose())
         .body(Body::empty())
         .unwrap();
 
+    RATE_LIMITS.acquire(token, Route::Profile).await;
     let response_result = client.request(request).await;
 
     if let Ok(response) = response_result {
+        let retry_after = if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            Some(retry_after_from_header(response.headers()).unwrap_or(DEFAULT_RETRY_AFTER))
+        } else {
+            None
+        };
+        RATE_LIMITS.update(token, Route::Profile, response.headers(), retry_after);
+
         match response.status() {
             StatusCode::OK => {
-                let streamed_bytes = hyper::body::to_bytes(response.into_body()).await;
-                if let Ok(bytes) = streamed_bytes {
-                    let body = String::from_utf8(bytes.to_vec()).expect("Received bad stream.");
-                    let profile = serde_json::from_str(&body).expect("Malformed response.");
-                    Ok(profile)
-                } else {
-                    Err(ProfileError::Other)
-                }
+                let bytes = hyper::body::to_bytes(response.into_body())
+                    .await
+                    .map_err(|_| ProfileError::Other)?;
+                serde_json::from_slice(&bytes).map_err(|_| ProfileError::Malformed)
             }
             StatusCode::UNAUTHORIZED => Err(ProfileError::Unauthorized),
             StatusCode::TOO_MANY_REQUESTS => Err(ProfileError::RateLimited),
@@ -0,0 +1,188 @@
+use crate::log_error_and_exit;
+use crate::matcher::{GiftMatch, GiftPattern, Matcher};
+use crate::scorer::ScorerKind;
+use colored::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Whether a message in the corpus really contains a gift code.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Label {
+    RealCode,
+    NotACode,
+}
+
+/// A single line of the corpus, like `{"content": "...", "label": "real_code"}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LabelledMessage {
+    pub content: String,
+    pub label: Label,
+    /// The line of the corpus file it came from, set while reading.
+    #[serde(skip)]
+    pub line: usize,
+}
+
+/// Counts of how predictions line up with the labels.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Confusion {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub true_negatives: usize,
+    pub false_negatives: usize,
+}
+
+impl Confusion {
+    pub fn add(&mut self, predicted: bool, label: Label) {
+        match (predicted, label) {
+            (true, Label::RealCode) => self.true_positives += 1,
+            (true, Label::NotACode) => self.false_positives += 1,
+            (false, Label::NotACode) => self.true_negatives += 1,
+            (false, Label::RealCode) => self.false_negatives += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.true_positives + self.false_positives + self.true_negatives + self.false_negatives
+    }
+
+    /// The share of accepted messages that really had a code, if any were accepted.
+    pub fn precision(&self) -> Option<f64> {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// The share of messages with a code that were accepted, if any had one.
+    pub fn recall(&self) -> Option<f64> {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+}
+
+fn ratio(part: usize, whole: usize) -> Option<f64> {
+    if whole == 0 {
+        None
+    } else {
+        Some(part as f64 / whole as f64)
+    }
+}
+
+/// A message the matcher got wrong, along with every candidate it scored.
+#[derive(Debug, Clone)]
+pub struct Mistake {
+    pub line: usize,
+    pub content: String,
+    pub candidates: Vec<GiftMatch>,
+}
+
+/// How one scorer did on the corpus.
+pub struct Evaluation {
+    pub scorer: ScorerKind,
+    pub threshold: f64,
+    /// Whether any candidate in a message was accepted, against its label.
+    pub overall: Confusion,
+    /// Per pattern, only over the messages that pattern found a candidate in.
+    pub per_pattern: Vec<(String, Confusion)>,
+    pub false_positives: Vec<Mistake>,
+    pub false_negatives: Vec<Mistake>,
+}
+
+/// Runs a matcher built from `patterns` and the given scorer over every message in the corpus.
+pub fn evaluate(
+    corpus: &[LabelledMessage],
+    patterns: Vec<GiftPattern>,
+    scorer: ScorerKind,
+    threshold: f64,
+) -> Result<Evaluation, String> {
+    let mut per_pattern: Vec<(String, Confusion)> = patterns
+        .iter()
+        .map(|p| (p.name.clone(), Confusion::default()))
+        .collect();
+    let matcher = Matcher::new(patterns)?.with_scorer(scorer.build(), threshold);
+
+    let mut evaluation = Evaluation {
+        scorer,
+        threshold,
+        overall: Confusion::default(),
+        per_pattern: Vec::new(),
+        false_positives: Vec::new(),
+        false_negatives: Vec::new(),
+    };
+
+    for message in corpus {
+        let candidates = matcher.score_gift_codes_in(std::iter::once(message.content.as_str()));
+
+        for (name, confusion) in per_pattern.iter_mut() {
+            let found: Vec<&GiftMatch> = candidates.iter().filter(|c| &c.pattern == name).collect();
+            if !found.is_empty() {
+                confusion.add(found.iter().any(|c| matcher.accepts(c)), message.label);
+            }
+        }
+
+        let accepted = candidates.iter().any(|c| matcher.accepts(c));
+        evaluation.overall.add(accepted, message.label);
+
+        let mistake = || Mistake {
+            line: message.line,
+            content: message.content.clone(),
+            candidates: candidates.clone(),
+        };
+        match (accepted, message.label) {
+            (true, Label::NotACode) => evaluation.false_positives.push(mistake()),
+            (false, Label::RealCode) => evaluation.false_negatives.push(mistake()),
+            _ => {}
+        }
+    }
+
+    evaluation.per_pattern = per_pattern;
+    Ok(evaluation)
+}
+
+/// Reads a JSONL corpus, skipping empty lines.
+pub fn read_corpus(path: &Path) -> Result<Vec<LabelledMessage>, EvalError> {
+    let file = File::open(path).map_err(|_| EvalError::FailedReading(path.to_path_buf()))?;
+    let mut corpus = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|_| EvalError::FailedReading(path.to_path_buf()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut message = serde_json::from_str::<LabelledMessage>(&line)
+            .map_err(|e| EvalError::MalformedLine(index + 1, e.to_string()))?;
+        message.line = index + 1;
+        corpus.push(message);
+    }
+
+    if corpus.is_empty() {
+        Err(EvalError::EmptyCorpus)
+    } else {
+        Ok(corpus)
+    }
+}
+
+pub enum EvalError {
+    FailedReading(PathBuf),
+    MalformedLine(usize, String),
+    EmptyCorpus,
+}
+
+impl EvalError {
+    pub fn handle(&self) {
+        match self {
+            EvalError::FailedReading(path) => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "I couldn't read {}...", path.display());
+            }
+            EvalError::MalformedLine(line, reason) => {
+                log_error_and_exit!("(x_x)", "Line {} of the corpus is broken: {}", line, reason);
+            }
+            EvalError::EmptyCorpus => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "There are no messages in that corpus.");
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::logging::LogBlock;
+use crate::pretty_info;
+use std::fmt;
+
+/// When to ask Discord what a gift code is for, as set with `gift_info_lookup`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GiftInfoLookup {
+    Off,
+    /// Look the code up first, and skip redeeming codes that are unknown or used up.
+    BeforeRedeem,
+    /// Only look up codes that were claimed or already redeemed.
+    AfterRedeem,
+}
+
+impl Default for GiftInfoLookup {
+    fn default() -> Self {
+        GiftInfoLookup::Off
+    }
+}
+
+/// What Discord's gift code endpoint tells us about a code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GiftInfo {
+    pub code: String,
+    #[serde(default)]
+    pub uses: u64,
+    #[serde(default)]
+    pub max_uses: u64,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    #[serde(default)]
+    pub redeemed: bool,
+    #[serde(default)]
+    pub subscription_plan: Option<SubscriptionPlan>,
+    #[serde(default)]
+    pub store_listing: Option<StoreListing>,
+    #[serde(default, rename = "user")]
+    pub gifter: Option<Gifter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionPlan {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreListing {
+    pub sku: Sku,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sku {
+    pub name: String,
+}
+
+/// The user who bought the gift.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Gifter {
+    pub username: String,
+    pub discriminator: String,
+}
+
+impl fmt::Display for Gifter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Accounts that moved to unique usernames have a discriminator of "0".
+        if self.discriminator == "0" {
+            write!(f, "{}", self.username)
+        } else {
+            write!(f, "{}#{:0>4}", self.username, self.discriminator)
+        }
+    }
+}
+
+impl GiftInfo {
+    /// The name of the plan or product, like `Nitro Monthly`.
+    pub fn name(&self) -> &str {
+        self.subscription_plan
+            .as_ref()
+            .map(|p| p.name.as_str())
+            .or_else(|| self.store_listing.as_ref().map(|l| l.sku.name.as_str()))
+            .unwrap_or("Unknown gift")
+    }
+
+    /// Whether redeeming the code can't work anymore.
+    pub fn is_used_up(&self) -> bool {
+        self.redeemed || (self.max_uses > 0 && self.uses >= self.max_uses)
+    }
+
+    pub fn log(&self, log: &mut LogBlock<'_>) {
+        pretty_info!(
+            log: log,
+            "(o·ω·o)",
+            "Gift: {} ({}/{} uses{}{})",
+            self.name(),
+            self.uses,
+            self.max_uses,
+            self.expires_at
+                .as_ref()
+                .map_or_else(String::new, |e| format!(", expires {}", e)),
+            self.gifter
+                .as_ref()
+                .map_or_else(String::new, |g| format!(", from {}", g))
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! The building blocks of RNS.
+//!
+//! The binary is only a thin front end over these modules, so the matcher,
+//! the redeem client, the config loader and the webhook sink can be reused
+//! on their own.
+
+pub mod cache;
+pub mod clients;
+pub mod config;
+pub mod connection;
+pub mod discord;
+pub mod eval;
+pub mod gift;
+pub mod matcher;
+pub mod redeem;
+pub mod reload;
+pub mod scorer;
+pub mod stats;
+pub mod store;
+pub mod token;
+pub mod util;
+pub mod vault;
+pub mod webhook;
+pub mod worker;
+#[macro_use]
+pub mod logging;
+
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde_derive;
+extern crate chrono;
+extern crate colored;
+extern crate fern;
+extern crate futures;
+extern crate hyper;
+extern crate hyper_tls;
+extern crate regex;
+extern crate serde;
+extern crate serde_json;
+extern crate serenity;
+extern crate tokio;
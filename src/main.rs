@@ -1,46 +1,275 @@
-mod cache;
-mod config;
-mod discord;
-mod matcher;
-mod util;
-mod webhook;
-#[macro_use]
-mod logging;
+mod cli;
 
-#[macro_use]
-extern crate lazy_static;
 #[macro_use]
 extern crate log;
-#[macro_use]
-extern crate serde_derive;
-extern crate chrono;
-extern crate colored;
-extern crate fern;
-extern crate futures;
-extern crate hyper;
-extern crate hyper_tls;
-extern crate regex;
-extern crate serde;
-extern crate serde_json;
-extern crate serenity;
-extern crate tokio;
+extern crate rust_nitro_sniper;
 
+use cli::{Command, Options, VaultAction};
 use colored::*;
-use hyper::{Body, Client};
-use hyper_tls::HttpsConnector;
-use serenity::Client as DiscordClient;
+use rust_nitro_sniper::clients::ClientPool;
+use rust_nitro_sniper::discord::HttpsClient;
+use rust_nitro_sniper::logging::ExitCode;
+use rust_nitro_sniper::scorer::ScorerKind;
+use rust_nitro_sniper::token::SecretToken;
+use rust_nitro_sniper::vault::{self, Vault, VaultError};
+use rust_nitro_sniper::{
+    config, connection, discord, eval, log_error_and_exit, logging, pretty_error, pretty_info,
+    pretty_success, pretty_warn, reload, stats, store, worker,
+};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+use structopt::StructOpt;
+
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 #[tokio::main]
 async fn main() {
-    logging::set_up_logger().expect("(o_O) Failed setting up logger. (HOW?)");
+    let options = Options::from_args();
+    logging::set_headless(options.headless || atty::isnt(atty::Stream::Stdin));
+    logging::set_up_logger(options.log_level, !options.no_color)
+        .expect("(o_O) Failed setting up logger. (HOW?)");
+
+    match options.command.unwrap_or(Command::Run) {
+        Command::Run => run(&options.config).await,
+        Command::CheckConfig => check_config(&options.config),
+        Command::VerifyTokens => {
+            verify_tokens(&options.config, &connection::build_client(false)).await
+        }
+        Command::Init { force } => init(&options.config, force),
+        Command::TestMatch { text } => test_match(&options.config, &text),
+        Command::EvalMatcher { corpus } => eval_matcher(&options.config, &corpus),
+        Command::Vault {
+            path,
+            key_file,
+            action,
+        } => manage_vault(&path, key_file.as_deref(), action),
+    }
+}
+
+fn check_config(path: &Path) {
+    let config = config::try_read_config(path)
+        .map_err(|e| e.handle())
+        .unwrap();
+    pretty_success!(
+        "o(»ω«)o",
+        "Your config looks fine! I'd snipe on {} account(s).",
+        config.get_all_sniping_tokens().len()
+    );
+}
+
+async fn verify_tokens(path: &Path, client: &HttpsClient) {
+    let config = config::try_read_config(path)
+        .map_err(|e| e.handle())
+        .unwrap();
+
+    let mut tokens = vec![config.main_token()];
+    tokens.extend(config.get_all_sniping_tokens());
+    tokens.sort();
+    tokens.dedup();
+
+    let mut failed = 0;
+    for token in &tokens {
+        match discord::get_profile_for_token(token, client, &config).await {
+            Ok(profile) => {
+                pretty_info!("(o·ω·o)", "{} belongs to {}.", token.label(), profile);
+            }
+            Err(e) => {
+                failed += 1;
+                let (kaomoji, text) = e.describe();
+                pretty_error!(kaomoji, "{}: {}", token.label(), text);
+            }
+        }
+    }
+
+    if failed > 0 {
+        log_error_and_exit!("(╥ω╥)", "{} token(s) couldn't be verified.", failed);
+    }
+    pretty_success!("o(»ω«)o", "All your tokens are fine!");
+}
+
+fn init(path: &Path, force: bool) {
+    config::write_default_config(path, force)
+        .map_err(|e| e.handle())
+        .unwrap();
+    pretty_success!(
+        "o(»ω«)o",
+        "Wrote the default config to {}. Now fill in your tokens!",
+        path.display()
+    );
+}
+
+fn test_match(path: &Path, text: &str) {
+    let config = if path.exists() {
+        config::try_read_config(path)
+            .map_err(|e| e.handle())
+            .unwrap()
+    } else {
+        config::Config::default()
+    };
+
+    let matcher = config.matcher();
+    let gift_matches = matcher.score_gift_codes_in(std::iter::once(text));
+    if gift_matches.is_empty() {
+        log_error_and_exit!("┐(¯ω¯;)┌", "I didn't find any gift code in there.");
+    }
+    for gift_match in gift_matches {
+        if matcher.accepts(&gift_match) {
+            pretty_success!(
+                "(°■°)!",
+                "Found code: {} (matched {}, score {:.3})",
+                gift_match.code,
+                gift_match.pattern,
+                gift_match.score
+            );
+        } else {
+            pretty_warn!(
+                "(・へ・)",
+                "Ignoring {} (matched {}): score {:.3} is below {}.",
+                gift_match.code,
+                gift_match.pattern,
+                gift_match.score,
+                matcher.threshold()
+            );
+        }
+    }
+}
+
+fn eval_matcher(path: &Path, corpus_path: &Path) {
+    let config = if path.exists() {
+        config::try_read_config(path)
+            .map_err(|e| e.handle())
+            .unwrap()
+    } else {
+        config::Config::default()
+    };
+    let corpus = eval::read_corpus(corpus_path)
+        .map_err(|e| e.handle())
+        .unwrap();
+    pretty_info!("(o·ω·o)", "Evaluating on {} message(s)...\n", corpus.len());
 
-    let https = HttpsConnector::new();
-    let client = Client::builder().build::<_, Body>(https);
+    for scorer in &ScorerKind::ALL {
+        let threshold = if *scorer == config.scorer() {
+            config.score_threshold()
+        } else {
+            scorer.default_threshold()
+        };
+        let evaluation = eval::evaluate(&corpus, config.gift_patterns(), *scorer, threshold)
+            .map_err(|e| {
+                log_error_and_exit!("(x_x)", "{}", e);
+            })
+            .unwrap();
+
+        pretty_info!(
+            "( ´-ω·)",
+            "Scorer {} at threshold {}:",
+            evaluation.scorer,
+            evaluation.threshold
+        );
+        print_confusion("all patterns", &evaluation.overall);
+        for (pattern, confusion) in &evaluation.per_pattern {
+            print_confusion(pattern, confusion);
+        }
+        for mistake in &evaluation.false_positives {
+            print_mistake("False positive", mistake);
+        }
+        for mistake in &evaluation.false_negatives {
+            print_mistake("False negative", mistake);
+        }
+        info!("");
+    }
+}
+
+fn print_confusion(name: &str, confusion: &eval::Confusion) {
+    let percentage = |value: Option<f64>| {
+        value.map_or_else(|| "-".to_string(), |v| format!("{:.1}%", v * 100_f64))
+    };
+    pretty_info!(
+        "->",
+        "{}: precision {}, recall {} (tp {}, fp {}, tn {}, fn {} of {})",
+        name,
+        percentage(confusion.precision()),
+        percentage(confusion.recall()),
+        confusion.true_positives,
+        confusion.false_positives,
+        confusion.true_negatives,
+        confusion.false_negatives,
+        confusion.total()
+    );
+}
+
+fn print_mistake(kind: &str, mistake: &eval::Mistake) {
+    let candidates = mistake
+        .candidates
+        .iter()
+        .map(|c| format!("{} via {} scored {:.3}", c.code, c.pattern, c.score))
+        .collect::<Vec<_>>();
+    let candidates = if candidates.is_empty() {
+        "no candidates".to_string()
+    } else {
+        candidates.join(", ")
+    };
+    pretty_warn!(
+        "(・へ・)",
+        "{} on line {}: {:?} ({})",
+        kind,
+        mistake.line,
+        mistake.content,
+        candidates
+    );
+}
+
+fn manage_vault(path: &Path, key_file: Option<&Path>, action: VaultAction) {
+    let passphrase = if path.exists() {
+        vault::read_passphrase(key_file)
+    } else {
+        vault::read_new_passphrase(key_file)
+    }
+    .map_err(|e| e.handle())
+    .unwrap();
+    let mut vault = Vault::open_or_create(path, passphrase)
+        .map_err(|e| e.handle())
+        .unwrap();
+
+    match action {
+        VaultAction::Add { label, main } => {
+            let token = vault::prompt_secret("Token: ")
+                .map_err(|_| VaultError::NoPassphrase.handle())
+                .unwrap();
+            vault
+                .add(label.clone(), SecretToken::new(token), main)
+                .map_err(|e| e.handle())
+                .unwrap();
+            vault.save().map_err(|e| e.handle()).unwrap();
+            pretty_success!("o(»ω«)o", "Added token {} to your vault!", label);
+        }
+        VaultAction::Remove { label } => {
+            vault.remove(&label).map_err(|e| e.handle()).unwrap();
+            vault.save().map_err(|e| e.handle()).unwrap();
+            pretty_success!("o(»ω«)o", "Removed token {} from your vault!", label);
+        }
+        VaultAction::List => {
+            if vault.entries().is_empty() {
+                pretty_info!("(o·ω·o)", "Your vault is empty.");
+            }
+            for entry in vault.entries() {
+                if vault.main_label() == Some(entry.label.as_str()) {
+                    pretty_info!("(o·ω·o)", "{} (main)", entry.label);
+                } else {
+                    pretty_info!("(o·ω·o)", "{}", entry.label);
+                }
+            }
+        }
+    }
+}
 
-    let config = config::try_read_config().map_err(|e| e.handle()).unwrap();
+async fn run(path: &Path) {
+    let config = config::try_read_config(path)
+        .map_err(|e| e.handle())
+        .unwrap();
+    let client = connection::connect_client(&config).await;
     let main_token = config.main_token();
-    let main_profile = discord::get_profile_for_token(&main_token, &client)
+    let main_profile = discord::get_profile_for_token(&main_token, &client, &config)
         .await
         .map_err(|e| e.handle())
         .unwrap();
@@ -68,39 +297,65 @@ async fn main() {
         sniping_tokens.len()
     );
 
-    let handler_info = Arc::new(discord::HandlerInfo::new(
-        client,
-        config,
-        sniping_tokens.len(),
-    ));
+    let seen_codes = store::open_store(
+        config.seen_codes_file(),
+        config.seen_codes_capacity(),
+        config.seen_codes_ttl(),
+    )
+    .map_err(|e| e.handle())
+    .unwrap();
 
-    let mut tasks = Vec::new();
-
-    for (index, token) in sniping_tokens.iter().enumerate() {
-        let discord_client_result = DiscordClient::builder(token)
-            .event_handler(discord::Handler::new(handler_info.clone()))
-            .await;
-
-        if let Ok(mut discord_client) = discord_client_result {
-            tasks.push(tokio::spawn(async move {
-                let connection_result = discord_client.start().await;
-                if connection_result.is_err() {
-                    pretty_error!(
-                        "(＃`Д´)",
-                        "Couldn't make a connection to Discord on token #{}. Is your token correct?",
-                        index
-                    );
-                }
-            }));
-        } else {
-            pretty_error!(
-                "(-_-;)°°°",
-                "Couldn't instantiate a Discord client for token #{}.",
-                index,
-            );
-        }
+    let stats = config
+        .stats_file()
+        .map(|path| stats::StatsFile::open(Path::new(&path)))
+        .transpose()
+        .map_err(|e| e.handle())
+        .unwrap();
+
+    let redeem_concurrency = config.redeem_concurrency();
+    let keep_alive_interval = config.keep_alive_interval();
+    connection::prewarm(&client, &config, redeem_concurrency).await;
+    let (redeem_queue, redeem_receiver) = worker::redeem_queue();
+    let handler_info = Arc::new(
+        discord::HandlerInfo::new(
+            client,
+            config,
+            seen_codes,
+            redeem_queue,
+            sniping_tokens.len(),
+        )
+        .with_stats(stats),
+    );
+    worker::spawn_workers(redeem_receiver, handler_info.clone(), redeem_concurrency);
+    if let Some(interval) = keep_alive_interval {
+        tokio::spawn(connection::keep_alive(
+            handler_info.clone(),
+            interval,
+            redeem_concurrency,
+        ));
     }
 
-    futures::future::join_all(tasks).await;
-    log_error_and_exit!("(x_x)", "Lost all connections.");
+    let compaction_info = handler_info.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(COMPACTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            compaction_info.compact_seen_codes().await;
+        }
+    });
+
+    let pool = ClientPool::new(handler_info.clone());
+    pool.sync(sniping_tokens).await;
+    tokio::spawn(reload::watch_config(
+        path.to_path_buf(),
+        pool.clone(),
+        handler_info,
+    ));
+
+    pool.wait_until_empty().await;
+    log_error_and_exit!(
+        code: ExitCode::ConnectionsLost,
+        "(x_x)",
+        "Lost all connections."
+    );
 }
@@ -1,58 +1,200 @@
+use crate::scorer::{CodeScorer, ScorerKind};
 use regex::Regex;
-use serenity::model::channel::Message;
-
-pub fn get_gift_code(message: &Message) -> Option<String> {
-    lazy_static! {
-        static ref GIFT_PATTERN: Regex = Regex::new(
-            "(discord.com/gifts/|discordapp.com/gifts/|discord.gift/)[ ]*([a-zA-Z0-9]{16,24})"
-        )
-        .unwrap();
-    }
-    let cleaned_content = sanitize_markdown(&message.content);
-    if let Some(captures) = GIFT_PATTERN.captures(&cleaned_content) {
-        let code = captures.get(2).unwrap().as_str();
-        if get_code_legitimacy_probability(code) > 0.9_f64 {
-            Some(code.to_string())
+use serenity::model::channel::{Embed, Message};
+use serenity::model::event::MessageUpdateEvent;
+
+/// A named gift link format, as configured in `gift_patterns`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GiftPattern {
+    pub name: String,
+    pub regex: String,
+    #[serde(default = "default_group")]
+    pub group: usize,
+    pub min_length: usize,
+    pub max_length: usize,
+}
+
+fn default_group() -> usize {
+    1
+}
+
+impl GiftPattern {
+    fn new(name: &str, regex: &str) -> Self {
+        GiftPattern {
+            name: name.to_string(),
+            regex: regex.to_string(),
+            group: 1,
+            min_length: 16,
+            max_length: 24,
+        }
+    }
+
+    /// Explains why this pattern can't be used, if it can't.
+    pub fn check(&self) -> Result<Regex, String> {
+        let regex = Regex::new(&self.regex).map_err(|e| e.to_string())?;
+        if self.group == 0 || self.group >= regex.captures_len() {
+            Err(format!("The regex has no capture group {}", self.group))
+        } else if self.min_length > self.max_length {
+            Err("min_length is bigger than max_length".to_string())
         } else {
-            None
+            Ok(regex)
         }
-    } else {
-        None
     }
 }
 
-fn sanitize_markdown(dirty_string: &str) -> String {
-    const MARKDOWN_CHARS: [char; 5] = ['*', '_', '`', '~', '|'];
-    let mut output = dirty_string.to_string();
-    output.retain(|c| !MARKDOWN_CHARS.contains(&c));
-    output
+/// The gift link formats RNS knows about out of the box.
+pub fn builtin_patterns() -> Vec<GiftPattern> {
+    vec![
+        GiftPattern::new("discord.gift", r"discord\.gift/[ ]*([a-zA-Z0-9]{16,24})"),
+        GiftPattern::new(
+            "discord.com/gifts",
+            r"discord\.com/gifts/[ ]*([a-zA-Z0-9]{16,24})",
+        ),
+        GiftPattern::new(
+            "discordapp.com/gifts",
+            r"discordapp\.com/gifts/[ ]*([a-zA-Z0-9]{16,24})",
+        ),
+    ]
 }
 
-fn get_code_legitimacy_probability(code: &str) -> f64 {
-    let (mut lower, mut upper, mut numeric) = (0, 0, 0);
+/// A candidate gift code, the name of the pattern that found it and its legitimacy score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GiftMatch {
+    pub code: String,
+    pub pattern: String,
+    pub score: f64,
+}
 
-    for c in code.chars() {
-        if c.is_ascii_digit() {
-            numeric += 1;
-        } else if c.is_uppercase() {
-            upper += 1;
-        } else {
-            lower += 1;
+/// Finds gift codes using a set of compiled gift patterns and a legitimacy scorer.
+pub struct Matcher {
+    patterns: Vec<(GiftPattern, Regex)>,
+    scorer: Box<dyn CodeScorer>,
+    threshold: f64,
+}
+
+impl Matcher {
+    /// Compiles the given patterns. Use `GiftPattern::check` first to get a useful error.
+    pub fn new(patterns: Vec<GiftPattern>) -> Result<Self, String> {
+        let patterns = patterns
+            .into_iter()
+            .map(|p| p.check().map(|r| (p, r)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let scorer = ScorerKind::default();
+        Ok(Matcher {
+            patterns,
+            scorer: scorer.build(),
+            threshold: scorer.default_threshold(),
+        })
+    }
+
+    /// Scores candidates with the given scorer and drops those below `threshold`.
+    pub fn with_scorer(mut self, scorer: Box<dyn CodeScorer>, threshold: f64) -> Self {
+        self.scorer = scorer;
+        self.threshold = threshold;
+        self
+    }
+
+    /// Whether a candidate scored high enough to be worth redeeming.
+    pub fn accepts(&self, gift_match: &GiftMatch) -> bool {
+        gift_match.score >= self.threshold
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Scores every distinct candidate in a Discord message, including its embeds.
+    pub fn score_message(&self, message: &Message) -> Vec<GiftMatch> {
+        self.score_gift_codes_in(message_texts(Some(&message.content), &message.embeds))
+    }
+
+    /// Scores every distinct candidate in the parts of a message an edit changed.
+    pub fn score_edit(&self, event: &MessageUpdateEvent) -> Vec<GiftMatch> {
+        let embeds = event.embeds.as_deref().unwrap_or_default();
+        self.score_gift_codes_in(message_texts(event.content.as_deref(), embeds))
+    }
+
+    /// Finds every distinct plausible gift code in a piece of text, in the order they appear.
+    pub fn find_gift_codes(&self, content: &str) -> Vec<GiftMatch> {
+        self.score_gift_codes_in(std::iter::once(content))
+            .into_iter()
+            .filter(|m| self.accepts(m))
+            .collect()
+    }
+
+    /// Scores every distinct candidate in several texts, in the order given.
+    ///
+    /// Unlike `find_gift_codes` this keeps candidates that scored too low, so they can be logged.
+    pub fn score_gift_codes_in<'a>(
+        &self,
+        texts: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<GiftMatch> {
+        let mut matches: Vec<GiftMatch> = Vec::new();
+        for text in texts {
+            for (code, pattern) in self.candidates(text) {
+                if !matches.iter().any(|m| m.code == code) {
+                    matches.push(GiftMatch {
+                        score: self.scorer.score(&code),
+                        code,
+                        pattern: pattern.name.clone(),
+                    });
+                }
+            }
         }
+        matches
     }
 
-    let length = code.len() as f64;
-    let percentage_lower = lower as f64 / length;
-    let percentage_upper = upper as f64 / length;
-    let percentage_numeric = numeric as f64 / length;
+    fn candidates(&self, content: &str) -> Vec<(String, &GiftPattern)> {
+        let cleaned_content = sanitize_markdown(content);
+        let mut candidates = self
+            .patterns
+            .iter()
+            .flat_map(|(pattern, regex)| {
+                regex
+                    .captures_iter(&cleaned_content)
+                    .filter_map(move |c| c.get(pattern.group))
+                    .filter(move |code| {
+                        let length = code.as_str().len();
+                        length >= pattern.min_length && length <= pattern.max_length
+                    })
+                    .map(move |code| (code.start(), code.as_str().to_string(), pattern))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(start, _, _)| *start);
+        candidates
+            .into_iter()
+            .map(|(_, code, pattern)| (code, pattern))
+            .collect()
+    }
+}
 
-    let chance_char = 26_f64 / 62_f64;
-    let chance_num = 10_f64 / 62_f64;
+/// Every text-bearing part of a message: its content, then each embed's text fields.
+fn message_texts<'a>(content: Option<&'a str>, embeds: &'a [Embed]) -> Vec<&'a str> {
+    let mut texts: Vec<&str> = content.into_iter().collect();
+    for embed in embeds {
+        texts.extend(embed.author.as_ref().map(|a| a.name.as_str()));
+        texts.extend(embed.title.as_deref());
+        texts.extend(embed.url.as_deref());
+        texts.extend(embed.description.as_deref());
+        for field in &embed.fields {
+            texts.push(&field.name);
+            texts.push(&field.value);
+        }
+        texts.extend(embed.footer.as_ref().map(|f| f.text.as_str()));
+    }
+    texts
+}
 
-    let error_lower = (chance_char - percentage_lower).abs();
-    let error_upper = (chance_char - percentage_upper).abs();
-    let error_numeric = (chance_num - percentage_numeric).abs();
+impl Default for Matcher {
+    fn default() -> Self {
+        Matcher::new(builtin_patterns()).unwrap()
+    }
+}
 
-    let error_amount = error_lower + error_upper + error_numeric;
-    1_f64 / (1_f64 + (error_amount * 25_f64 - 15_f64).exp())
-}
\ No newline at end of file
+/// Strips the markdown characters people wrap gift links in.
+pub fn sanitize_markdown(dirty_string: &str) -> String {
+    const MARKDOWN_CHARS: [char; 5] = ['*', '_', '`', '~', '|'];
+    let mut output = dirty_string.to_string();
+    output.retain(|c| !MARKDOWN_CHARS.contains(&c));
+    output
+}
@@ -0,0 +1,201 @@
+use crate::logging::LogBlock;
+use crate::{pretty_error, pretty_info, pretty_success, pretty_warn};
+use hyper::header::{HeaderMap, RETRY_AFTER};
+use hyper::StatusCode;
+use std::time::Duration;
+
+const UNKNOWN_GIFT_CODE: u64 = 10038;
+const ALREADY_REDEEMED: u64 = 50050;
+/// The longest rate limit wait we believe, so a bogus header can't stall us forever.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60 * 60);
+
+/// What happened when we tried redeeming a gift code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedeemOutcome {
+    Claimed,
+    AlreadyRedeemed,
+    UnknownCode,
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
+    Unauthorized,
+    ServerError(StatusCode),
+    TransportError,
+    TimedOut,
+    /// Not redeemed, because the gift info said every use is gone.
+    SkippedUsedUp,
+    /// Not redeemed, because the gift info said Discord doesn't know the code.
+    SkippedUnknown,
+    UnknownStatus {
+        status: StatusCode,
+        body: Option<String>,
+    },
+}
+
+/// The JSON error body Discord sends along with failed requests.
+#[derive(Debug, Deserialize)]
+pub struct DiscordError {
+    #[serde(default)]
+    pub code: u64,
+    pub message: String,
+    #[serde(default)]
+    pub retry_after: Option<f64>,
+}
+
+impl RedeemOutcome {
+    pub fn from_response(status: StatusCode, headers: &HeaderMap, body: Option<String>) -> Self {
+        let error = body
+            .as_ref()
+            .and_then(|b| serde_json::from_str::<DiscordError>(b).ok());
+
+        match status {
+            StatusCode::OK => RedeemOutcome::Claimed,
+            StatusCode::TOO_MANY_REQUESTS => RedeemOutcome::RateLimited {
+                retry_after: retry_after_from_header(headers).or_else(|| {
+                    error
+                        .as_ref()
+                        .and_then(|e| e.retry_after)
+                        .and_then(rate_limit_wait)
+                }),
+            },
+            StatusCode::UNAUTHORIZED => RedeemOutcome::Unauthorized,
+            StatusCode::METHOD_NOT_ALLOWED => RedeemOutcome::ServerError(status),
+            s if s.is_server_error() => RedeemOutcome::ServerError(status),
+            _ => match error.map(|e| e.code) {
+                Some(UNKNOWN_GIFT_CODE) => RedeemOutcome::UnknownCode,
+                Some(ALREADY_REDEEMED) => RedeemOutcome::AlreadyRedeemed,
+                _ => match status {
+                    StatusCode::NOT_FOUND => RedeemOutcome::UnknownCode,
+                    StatusCode::BAD_REQUEST => RedeemOutcome::AlreadyRedeemed,
+                    _ => RedeemOutcome::UnknownStatus { status, body },
+                },
+            },
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RedeemOutcome::Claimed => "claimed",
+            RedeemOutcome::AlreadyRedeemed => "already_redeemed",
+            RedeemOutcome::UnknownCode => "unknown_code",
+            RedeemOutcome::RateLimited { .. } => "rate_limited",
+            RedeemOutcome::Unauthorized => "unauthorized",
+            RedeemOutcome::ServerError(_) => "server_error",
+            RedeemOutcome::TransportError => "transport_error",
+            RedeemOutcome::TimedOut => "timed_out",
+            RedeemOutcome::SkippedUsedUp => "skipped_used_up",
+            RedeemOutcome::SkippedUnknown => "skipped_unknown",
+            RedeemOutcome::UnknownStatus { .. } => "unknown_status",
+        }
+    }
+
+    pub fn is_claimed(&self) -> bool {
+        matches!(self, RedeemOutcome::Claimed)
+    }
+
+    pub fn log(&self, log: &mut LogBlock<'_>) {
+        match self {
+            RedeemOutcome::Claimed => {
+                pretty_success!(log: log, "o(»ω«)o", "Yay! Claimed code!");
+            }
+            RedeemOutcome::AlreadyRedeemed => {
+                pretty_error!(log: log, "(╥ω╥)", "Code was already redeemed.");
+            }
+            RedeemOutcome::UnknownCode => {
+                pretty_warn!(log: log, "(╥ω╥)", "Code was fake or expired.");
+            }
+            RedeemOutcome::RateLimited { retry_after } => {
+                if let Some(retry_after) = retry_after {
+                    pretty_warn!(
+                        log: log,
+                        "(x_x)",
+                        "We were rate-limited... (retry after {}ms)",
+                        retry_after.as_millis()
+                    );
+                } else {
+                    pretty_warn!(log: log, "(x_x)", "We were rate-limited...");
+                }
+            }
+            RedeemOutcome::Unauthorized => {
+                pretty_error!(
+                    log: log,
+                    "(＃`Д´)",
+                    "Discord didn't accept your main token."
+                );
+            }
+            RedeemOutcome::ServerError(_) => {
+                pretty_error!(log: log, "(x_x)", "There was an error on Discord's side.");
+            }
+            RedeemOutcome::TransportError => {
+                pretty_warn!(
+                    log: log,
+                    "┐(¯ω¯;)┌",
+                    "Requesting failed. Check your connection!"
+                );
+            }
+            RedeemOutcome::TimedOut => {
+                pretty_warn!(
+                    log: log,
+                    "┐(¯ω¯;)┌",
+                    "Discord took too long to answer, giving up on this code."
+                );
+            }
+            RedeemOutcome::SkippedUsedUp => {
+                pretty_info!(
+                    log: log,
+                    "(・へ・)",
+                    "Didn't redeem, the gift info says it's used up."
+                );
+            }
+            RedeemOutcome::SkippedUnknown => {
+                pretty_info!(
+                    log: log,
+                    "(・へ・)",
+                    "Didn't redeem, the gift info says the code doesn't exist."
+                );
+            }
+            RedeemOutcome::UnknownStatus { status, body } => {
+                pretty_error!(
+                    log: log,
+                    "┐(¯ω¯;)┌",
+                    "Received unknown response... ({}{})",
+                    status.as_str(),
+                    status
+                        .canonical_reason()
+                        .map_or_else(|| "".to_string(), |r| format!(" {}", r))
+                );
+                if let Some(body) = body {
+                    pretty_error!(log: log, "->", "...with this body: {}", body);
+                } else {
+                    pretty_error!(
+                        log: log,
+                        "->",
+                        "...and couldn't parse the body of the response."
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// The `Retry-After` header of a 429 response, in seconds.
+pub fn retry_after_from_header(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<f64>().ok())
+        .and_then(rate_limit_wait)
+}
+
+/// Turns a wait in seconds from Discord into a duration, capped at `MAX_RATE_LIMIT_WAIT`.
+///
+/// Negative, NaN and infinite values are rejected.
+pub fn rate_limit_wait(seconds: f64) -> Option<Duration> {
+    if !seconds.is_finite() || seconds < 0_f64 {
+        None
+    } else if seconds >= MAX_RATE_LIMIT_WAIT.as_secs_f64() {
+        Some(MAX_RATE_LIMIT_WAIT)
+    } else {
+        Some(Duration::from_secs_f64(seconds))
+    }
+}
@@ -0,0 +1,81 @@
+use crate::clients::ClientPool;
+use crate::config;
+use crate::discord::HandlerInfo;
+use crate::{pretty_error, pretty_info};
+use colored::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Reloads the config whenever the file changes or, on Unix, when we receive SIGHUP.
+pub async fn watch_config(path: PathBuf, pool: Arc<ClientPool>, info: Arc<HandlerInfo>) {
+    let reloader = Arc::new(Reloader {
+        path,
+        pool,
+        info,
+        lock: Mutex::new(()),
+    });
+
+    #[cfg(unix)]
+    {
+        let reloader = reloader.clone();
+        tokio::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+            if let Ok(mut hangups) = signal(SignalKind::hangup()) {
+                while hangups.recv().await.is_some() {
+                    reloader.reload().await;
+                }
+            }
+        });
+    }
+
+    let mut last_modified = modified_at(&reloader.path);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let modified = modified_at(&reloader.path);
+        if modified.is_some() && modified != last_modified {
+            last_modified = modified;
+            reloader.reload().await;
+        }
+    }
+}
+
+struct Reloader {
+    path: PathBuf,
+    pool: Arc<ClientPool>,
+    info: Arc<HandlerInfo>,
+    lock: Mutex<()>,
+}
+
+impl Reloader {
+    async fn reload(&self) {
+        let _guard = self.lock.lock().await;
+
+        match config::try_read_config(&self.path) {
+            Ok(config) => {
+                let mut sniping_tokens = config.get_all_sniping_tokens();
+                sniping_tokens.sort();
+                sniping_tokens.dedup();
+
+                self.info.replace_config(config, sniping_tokens.len());
+                self.pool.sync(sniping_tokens).await;
+                pretty_info!("(o·ω·o)", "Reloaded your config!");
+            }
+            Err(e) => {
+                pretty_error!(
+                    "┐(¯ω¯;)┌",
+                    "I couldn't reload your config, so I'll keep using the old one."
+                );
+                pretty_error!("->", "...{}", e);
+            }
+        }
+    }
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// How many characters a gift code can be made of: `a-z`, `A-Z` and `0-9`.
+const ALPHABET_SIZE: f64 = 62_f64;
+
+/// Rates how likely a string is to be a real, randomly generated gift code, from 0 to 1.
+pub trait CodeScorer: Send + Sync {
+    fn score(&self, code: &str) -> f64;
+}
+
+/// Compares the mix of lowercase, uppercase and numeric characters to that of a random code.
+pub struct LogisticScorer;
+
+impl LogisticScorer {
+    /// How fast the score falls off as the character mix gets worse.
+    const STEEPNESS: f64 = 25_f64;
+    /// How much error a code can have before its score drops below 0.5.
+    const MIDPOINT: f64 = 15_f64;
+}
+
+impl CodeScorer for LogisticScorer {
+    fn score(&self, code: &str) -> f64 {
+        if code.is_empty() {
+            return 0_f64;
+        }
+
+        let (mut lower, mut upper, mut numeric) = (0, 0, 0);
+
+        for c in code.chars() {
+            if c.is_ascii_digit() {
+                numeric += 1;
+            } else if c.is_uppercase() {
+                upper += 1;
+            } else {
+                lower += 1;
+            }
+        }
+
+        let length = code.len() as f64;
+        let percentage_lower = lower as f64 / length;
+        let percentage_upper = upper as f64 / length;
+        let percentage_numeric = numeric as f64 / length;
+
+        let chance_char = 26_f64 / ALPHABET_SIZE;
+        let chance_num = 10_f64 / ALPHABET_SIZE;
+
+        let error_lower = (chance_char - percentage_lower).abs();
+        let error_upper = (chance_char - percentage_upper).abs();
+        let error_numeric = (chance_num - percentage_numeric).abs();
+
+        let error_amount = error_lower + error_upper + error_numeric;
+        1_f64 / (1_f64 + (error_amount * Self::STEEPNESS - Self::MIDPOINT).exp())
+    }
+}
+
+/// The Shannon entropy of the code, relative to the most a string of its length can have.
+///
+/// Catches repetitive strings like `aaaaBBBB11112222` that have a plausible character mix.
+pub struct EntropyScorer;
+
+impl CodeScorer for EntropyScorer {
+    fn score(&self, code: &str) -> f64 {
+        let length = code.chars().count();
+        if length < 2 {
+            return 0_f64;
+        }
+
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        for c in code.chars() {
+            *counts.entry(c).or_insert(0) += 1;
+        }
+
+        let entropy: f64 = counts
+            .values()
+            .map(|&count| {
+                let p = count as f64 / length as f64;
+                -p * p.log2()
+            })
+            .sum();
+        // Strings longer than the alphabet can use characters from outside of it.
+        let max_entropy = (length as f64).min(ALPHABET_SIZE).log2();
+        (entropy / max_entropy).min(1_f64)
+    }
+}
+
+/// Gives every code a perfect score, leaving it to the patterns alone.
+pub struct AcceptAllScorer;
+
+impl CodeScorer for AcceptAllScorer {
+    fn score(&self, _code: &str) -> f64 {
+        1_f64
+    }
+}
+
+/// The scorers that can be picked with `scorer` in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScorerKind {
+    Logistic,
+    Entropy,
+    AcceptAll,
+}
+
+impl ScorerKind {
+    pub const ALL: [ScorerKind; 3] = [
+        ScorerKind::Logistic,
+        ScorerKind::Entropy,
+        ScorerKind::AcceptAll,
+    ];
+
+    pub fn build(self) -> Box<dyn CodeScorer> {
+        match self {
+            ScorerKind::Logistic => Box::new(LogisticScorer),
+            ScorerKind::Entropy => Box::new(EntropyScorer),
+            ScorerKind::AcceptAll => Box::new(AcceptAllScorer),
+        }
+    }
+
+    /// The threshold used when `score_threshold` isn't set.
+    pub fn default_threshold(self) -> f64 {
+        match self {
+            ScorerKind::Logistic => 0.9,
+            ScorerKind::Entropy => 0.85,
+            ScorerKind::AcceptAll => 0_f64,
+        }
+    }
+}
+
+impl Default for ScorerKind {
+    fn default() -> Self {
+        ScorerKind::Logistic
+    }
+}
+
+impl fmt::Display for ScorerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScorerKind::Logistic => write!(f, "logistic"),
+            ScorerKind::Entropy => write!(f, "entropy"),
+            ScorerKind::AcceptAll => write!(f, "accept_all"),
+        }
+    }
+}
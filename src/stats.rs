@@ -0,0 +1,90 @@
+use crate::logging::Timeline;
+use crate::{log_error_and_exit, pretty_error};
+use colored::*;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// One phase of a claim attempt, in milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Phase {
+    pub name: String,
+    pub ms: f64,
+}
+
+/// The timings of a claim attempt, as written to `stats_file`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimTimings {
+    pub at: i64,
+    pub code: String,
+    pub outcome: String,
+    pub total_ms: f64,
+    pub phases: Vec<Phase>,
+}
+
+impl ClaimTimings {
+    pub fn new(code: &str, outcome: &str, timeline: &Timeline) -> Self {
+        ClaimTimings {
+            at: chrono::Utc::now().timestamp(),
+            code: code.to_string(),
+            outcome: outcome.to_string(),
+            total_ms: milliseconds(timeline.elapsed().as_secs_f64()),
+            phases: timeline
+                .spans()
+                .iter()
+                .map(|(name, duration)| Phase {
+                    name: name.to_string(),
+                    ms: milliseconds(duration.as_secs_f64()),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn milliseconds(seconds: f64) -> f64 {
+    seconds * 1000_f64
+}
+
+/// Appends the timings of every claim attempt to a JSON lines file, to be aggregated elsewhere.
+pub struct StatsFile {
+    file: Mutex<File>,
+}
+
+impl StatsFile {
+    pub fn open(path: &Path) -> Result<Self, StatsError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|_| StatsError::FailedOpening(path.to_path_buf()))?;
+        Ok(StatsFile {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, timings: &ClaimTimings) {
+        let line = serde_json::to_string(timings).unwrap();
+        if writeln!(self.file.lock().unwrap(), "{}", line).is_err() {
+            pretty_error!("(x_x)", "Failed writing the timings to the stats file.");
+        }
+    }
+}
+
+pub enum StatsError {
+    FailedOpening(PathBuf),
+}
+
+impl StatsError {
+    pub fn handle(&self) {
+        match self {
+            StatsError::FailedOpening(path) => {
+                log_error_and_exit!(
+                    "┐(¯ω¯;)┌",
+                    "I couldn't open the stats file {}...",
+                    path.display()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use crate::gift::GiftInfo;
+use crate::{log_error_and_exit, pretty_error};
+use colored::*;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A gift code we've already seen, along with who saw it and how redeeming it went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeenCode {
+    pub code: String,
+    pub seen_at: i64,
+    pub account: String,
+    pub outcome: Option<String>,
+    #[serde(default)]
+    pub gift: Option<GiftInfo>,
+}
+
+impl SeenCode {
+    pub fn new(code: String, account: String) -> Self {
+        SeenCode {
+            code,
+            seen_at: chrono::Utc::now().timestamp(),
+            account,
+            outcome: None,
+            gift: None,
+        }
+    }
+}
+
+/// Remembers which gift codes were already tried, so we never request one twice.
+pub trait SeenCodeStore: Send {
+    fn contains(&self, code: &str) -> bool;
+
+    fn insert(&mut self, entry: SeenCode);
+
+    fn record_outcome(&mut self, code: &str, outcome: &str);
+
+    fn record_gift_info(&mut self, code: &str, gift: &GiftInfo);
+
+    /// Forgets a code, as if it had never been seen.
+    fn forget(&mut self, code: &str);
+
+    fn compact(&mut self) {}
+}
+
+/// Keeps seen codes in memory only, so they are forgotten on restart.
+///
+/// Holds at most `capacity` codes and forgets codes older than `ttl`, dropping
+/// the oldest ones first. Lookups stay O(1) no matter how full it gets.
+pub struct MemoryStore {
+    codes: HashMap<String, (SeenCode, u64)>,
+    order: VecDeque<(String, u64)>,
+    next_sequence: u64,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        MemoryStore {
+            codes: HashMap::new(),
+            order: VecDeque::new(),
+            next_sequence: 0,
+            capacity,
+            ttl,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Iterates over all remembered codes, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &SeenCode> {
+        self.order
+            .iter()
+            .filter_map(move |(code, sequence)| match self.codes.get(code) {
+                Some((entry, s)) if s == sequence => Some(entry),
+                _ => None,
+            })
+    }
+
+    fn is_expired(&self, entry: &SeenCode, now: i64) -> bool {
+        let ttl = i64::try_from(self.ttl.as_secs()).unwrap_or(i64::MAX);
+        now.saturating_sub(entry.seen_at) > ttl
+    }
+
+    fn evict(&mut self) {
+        let now = chrono::Utc::now().timestamp();
+        while let Some((code, sequence)) = self.order.front() {
+            let should_pop = match self.codes.get(code) {
+                // The code was inserted again later, so this is a stale position.
+                Some((_, s)) if s != sequence => true,
+                Some((entry, _)) => self.codes.len() > self.capacity || self.is_expired(entry, now),
+                None => true,
+            };
+            if !should_pop {
+                break;
+            }
+
+            let (code, sequence) = self.order.pop_front().unwrap();
+            if self.codes.get(&code).map_or(false, |(_, s)| *s == sequence) {
+                self.codes.remove(&code);
+            }
+        }
+    }
+}
+
+impl SeenCodeStore for MemoryStore {
+    fn contains(&self, code: &str) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        self.codes
+            .get(code)
+            .map_or(false, |(entry, _)| !self.is_expired(entry, now))
+    }
+
+    fn insert(&mut self, entry: SeenCode) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.order.push_back((entry.code.clone(), sequence));
+        self.codes.insert(entry.code.clone(), (entry, sequence));
+        self.evict();
+    }
+
+    fn record_outcome(&mut self, code: &str, outcome: &str) {
+        if let Some((entry, _)) = self.codes.get_mut(code) {
+            entry.outcome = Some(outcome.to_string());
+        }
+    }
+
+    fn record_gift_info(&mut self, code: &str, gift: &GiftInfo) {
+        if let Some((entry, _)) = self.codes.get_mut(code) {
+            entry.gift = Some(gift.clone());
+        }
+    }
+
+    fn forget(&mut self, code: &str) {
+        // Its position in `order` goes stale and is dropped on the next eviction.
+        self.codes.remove(code);
+    }
+
+    fn compact(&mut self) {
+        self.evict();
+    }
+}
+
+/// Keeps seen codes in an append-only JSON lines file next to an in-memory index.
+///
+/// Every change is appended as a full entry and the last entry for a code wins,
+/// so compacting only has to rewrite the file with the current index.
+pub struct FileStore {
+    path: PathBuf,
+    file: File,
+    memory: MemoryStore,
+}
+
+impl FileStore {
+    pub fn open(
+        path: impl Into<PathBuf>,
+        capacity: usize,
+        ttl: Duration,
+    ) -> Result<Self, StoreError> {
+        let path = path.into();
+        let mut memory = MemoryStore::new(capacity, ttl);
+
+        if path.exists() {
+            let file = File::open(&path).map_err(|_| StoreError::FailedReading)?;
+            let mut entries = HashMap::new();
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|_| StoreError::FailedReading)?;
+                // A crash mid-append can leave a torn last line behind, which we just skip.
+                if let Ok(entry) = serde_json::from_str::<SeenCode>(&line) {
+                    entries.insert(entry.code.clone(), entry);
+                }
+            }
+
+            let mut entries = entries.into_iter().map(|(_, e)| e).collect::<Vec<_>>();
+            entries.sort_by_key(|e| e.seen_at);
+            for entry in entries {
+                memory.insert(entry);
+            }
+        }
+
+        let file = open_for_append(&path)?;
+        let mut store = FileStore { path, file, memory };
+        store.compact();
+        Ok(store)
+    }
+
+    fn append(&mut self, entry: &SeenCode) {
+        let line = serde_json::to_string(entry).unwrap();
+        if writeln!(self.file, "{}", line).is_err() {
+            pretty_error!(
+                "(x_x)",
+                "Failed writing to the seen codes file, this code might be tried again after a restart."
+            );
+        }
+    }
+
+    fn try_compact(&mut self) -> Result<(), StoreError> {
+        let temp_path = self.path.with_extension("tmp");
+        let mut temp_file = File::create(&temp_path).map_err(|_| StoreError::FailedWriting)?;
+        for entry in self.memory.entries() {
+            writeln!(temp_file, "{}", serde_json::to_string(entry).unwrap())
+                .map_err(|_| StoreError::FailedWriting)?;
+        }
+        temp_file
+            .sync_all()
+            .map_err(|_| StoreError::FailedWriting)?;
+        fs::rename(&temp_path, &self.path).map_err(|_| StoreError::FailedWriting)?;
+        self.file = open_for_append(&self.path)?;
+        Ok(())
+    }
+}
+
+impl SeenCodeStore for FileStore {
+    fn contains(&self, code: &str) -> bool {
+        self.memory.contains(code)
+    }
+
+    fn insert(&mut self, entry: SeenCode) {
+        self.append(&entry);
+        self.memory.insert(entry);
+    }
+
+    fn record_outcome(&mut self, code: &str, outcome: &str) {
+        self.memory.record_outcome(code, outcome);
+        if let Some((entry, _)) = self.memory.codes.get(code).cloned() {
+            self.append(&entry);
+        }
+    }
+
+    fn record_gift_info(&mut self, code: &str, gift: &GiftInfo) {
+        self.memory.record_gift_info(code, gift);
+        if let Some((entry, _)) = self.memory.codes.get(code).cloned() {
+            self.append(&entry);
+        }
+    }
+
+    fn forget(&mut self, code: &str) {
+        // There's no way to take back an appended line, but forgetting is rare enough
+        // that rewriting the whole file is fine.
+        if self.memory.contains(code) {
+            self.memory.forget(code);
+            self.compact();
+        }
+    }
+
+    fn compact(&mut self) {
+        self.memory.compact();
+        if self.try_compact().is_err() {
+            pretty_error!("(x_x)", "Failed compacting the seen codes file.");
+        }
+    }
+}
+
+fn open_for_append(path: &Path) -> Result<File, StoreError> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|_| StoreError::FailedWriting)
+}
+
+/// Opens the file-backed store if a path is given, otherwise an in-memory one.
+pub fn open_store(
+    path: Option<String>,
+    capacity: usize,
+    ttl: Duration,
+) -> Result<Box<dyn SeenCodeStore>, StoreError> {
+    if let Some(path) = path {
+        Ok(Box::new(FileStore::open(path, capacity, ttl)?))
+    } else {
+        Ok(Box::new(MemoryStore::new(capacity, ttl)))
+    }
+}
+
+pub enum StoreError {
+    FailedReading,
+    FailedWriting,
+}
+
+impl StoreError {
+    pub fn handle(&self) {
+        match self {
+            StoreError::FailedReading => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "I couldn't read your seen codes file...");
+            }
+            StoreError::FailedWriting => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "I couldn't write to your seen codes file...");
+            }
+        }
+    }
+}
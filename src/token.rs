@@ -0,0 +1,92 @@
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// A Discord token that never shows up in `Debug` or `Display` output.
+///
+/// Use `expose` only where the raw value is really needed, like the `Authorization`
+/// header. Logs should name the account with `label` instead.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretToken {
+    value: String,
+    #[serde(skip)]
+    label: Option<String>,
+}
+
+impl SecretToken {
+    pub fn new(value: impl Into<String>) -> Self {
+        SecretToken {
+            value: value.into(),
+            label: None,
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// The first 8 hex characters of the token's SHA-256, stable across restarts.
+    pub fn fingerprint(&self) -> String {
+        Sha256::digest(self.value.as_bytes())
+            .iter()
+            .take(4)
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// A name for the account that is safe to log: its vault label or its fingerprint.
+    pub fn label(&self) -> String {
+        self.label
+            .clone()
+            .unwrap_or_else(|| format!("token-{}", self.fingerprint()))
+    }
+}
+
+impl fmt::Debug for SecretToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretToken({})", self.label())
+    }
+}
+
+impl fmt::Display for SecretToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted {}>", self.label())
+    }
+}
+
+impl PartialEq for SecretToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for SecretToken {}
+
+impl PartialOrd for SecretToken {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SecretToken {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl Hash for SecretToken {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
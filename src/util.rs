@@ -1,5 +1,39 @@
 use serenity::model::user::User;
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
 
 pub fn user_to_tag(user: &User) -> String {
     format!("{}#{:0>4}", user.name, user.discriminator)
 }
+
+/// Remembers the last `capacity` distinct items, forgetting the oldest first.
+pub struct RecentlySeen<T> {
+    items: HashSet<T>,
+    order: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T: Hash + Eq + Clone> RecentlySeen<T> {
+    pub fn new(capacity: usize) -> Self {
+        RecentlySeen {
+            items: HashSet::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Remembers the item, returning whether it's new.
+    pub fn insert(&mut self, item: T) -> bool {
+        if self.items.contains(&item) {
+            return false;
+        }
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.items.remove(&oldest);
+            }
+        }
+        self.items.insert(item.clone());
+        self.order.push_back(item);
+        true
+    }
+}
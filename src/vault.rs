@@ -0,0 +1,342 @@
+use crate::token::SecretToken;
+use crate::{log_error_and_exit, pretty_error};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use colored::*;
+use std::fs::{self, File, OpenOptions};
+use std::io::{stdin, Write};
+use std::path::{Path, PathBuf};
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: u32 = 32;
+
+/// Bounds for the KDF parameters read from a vault file, in KiB and passes.
+/// Anything weaker was not written by RNS, and anything bigger could exhaust memory.
+const MIN_MEM_COST: u32 = 19 * 1024;
+const MAX_MEM_COST: u32 = 1024 * 1024;
+const MIN_TIME_COST: u32 = 2;
+const MAX_TIME_COST: u32 = 16;
+const MAX_LANES: u32 = 16;
+
+/// A token stored in the vault, identified by a label of your choice.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VaultEntry {
+    pub label: String,
+    token: SecretToken,
+}
+
+impl VaultEntry {
+    /// The stored token, labelled with the entry's label.
+    pub fn token(&self) -> SecretToken {
+        self.token.clone().with_label(self.label.clone())
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct VaultContents {
+    main: Option<String>,
+    entries: Vec<VaultEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KdfParams {
+    mem_cost: u32,
+    time_cost: u32,
+    lanes: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            mem_cost: 64 * 1024,
+            time_cost: 3,
+            lanes: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn is_within_bounds(&self) -> bool {
+        (MIN_MEM_COST..=MAX_MEM_COST).contains(&self.mem_cost)
+            && (MIN_TIME_COST..=MAX_TIME_COST).contains(&self.time_cost)
+            && (1..=MAX_LANES).contains(&self.lanes)
+    }
+}
+
+/// The vault as it's stored on disk: Argon2id for the key, ChaCha20-Poly1305 for the contents.
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u8,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// An unlocked token vault.
+pub struct Vault {
+    path: PathBuf,
+    passphrase: String,
+    contents: VaultContents,
+}
+
+impl Vault {
+    /// Unlocks the vault at `path`, or starts an empty one if there's no file yet.
+    pub fn open_or_create(path: &Path, passphrase: String) -> Result<Self, VaultError> {
+        if path.exists() {
+            Vault::open(path, passphrase)
+        } else {
+            Ok(Vault {
+                path: path.to_path_buf(),
+                passphrase,
+                contents: VaultContents::default(),
+            })
+        }
+    }
+
+    pub fn open(path: &Path, passphrase: String) -> Result<Self, VaultError> {
+        let raw = fs::read_to_string(path).map_err(|_| VaultError::FailedReading)?;
+        let file = serde_json::from_str::<VaultFile>(&raw).map_err(|_| VaultError::Malformed)?;
+        let salt = base64::decode(&file.salt).map_err(|_| VaultError::Malformed)?;
+        let nonce = base64::decode(&file.nonce).map_err(|_| VaultError::Malformed)?;
+        let ciphertext = base64::decode(&file.ciphertext).map_err(|_| VaultError::Malformed)?;
+        if nonce.len() != NONCE_LENGTH {
+            return Err(VaultError::Malformed);
+        }
+        if !file.kdf.is_within_bounds() {
+            return Err(VaultError::UnsafeKdf);
+        }
+
+        let cipher = make_cipher(&passphrase, &salt, &file.kdf)?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| VaultError::WrongPassphrase)?;
+        let contents = serde_json::from_slice::<VaultContents>(&plaintext)
+            .map_err(|_| VaultError::Malformed)?;
+
+        Ok(Vault {
+            path: path.to_path_buf(),
+            passphrase,
+            contents,
+        })
+    }
+
+    /// Encrypts the vault with a fresh salt and nonce and writes it to disk.
+    pub fn save(&self) -> Result<(), VaultError> {
+        let mut salt = [0_u8; SALT_LENGTH];
+        let mut nonce = [0_u8; NONCE_LENGTH];
+        getrandom::getrandom(&mut salt).map_err(|_| VaultError::FailedWriting)?;
+        getrandom::getrandom(&mut nonce).map_err(|_| VaultError::FailedWriting)?;
+
+        let kdf = KdfParams::default();
+        let cipher = make_cipher(&self.passphrase, &salt, &kdf)?;
+        let plaintext = serde_json::to_vec(&self.contents).unwrap();
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+            .map_err(|_| VaultError::FailedWriting)?;
+
+        let file = VaultFile {
+            version: 1,
+            kdf,
+            salt: base64::encode(&salt),
+            nonce: base64::encode(&nonce),
+            ciphertext: base64::encode(&ciphertext),
+        };
+
+        // Synced before the rename, so a crash can't replace a good vault with a torn one.
+        let temp_path = self.path.with_extension("tmp");
+        let mut temp_file = create_private(&temp_path).map_err(|_| VaultError::FailedWriting)?;
+        temp_file
+            .write_all(serde_json::to_string_pretty(&file).unwrap().as_bytes())
+            .map_err(|_| VaultError::FailedWriting)?;
+        temp_file
+            .sync_all()
+            .map_err(|_| VaultError::FailedWriting)?;
+        fs::rename(&temp_path, &self.path).map_err(|_| VaultError::FailedWriting)
+    }
+
+    pub fn add(&mut self, label: String, token: SecretToken, main: bool) -> Result<(), VaultError> {
+        if self.contents.entries.iter().any(|e| e.label == label) {
+            return Err(VaultError::DuplicateLabel(label));
+        }
+        if main {
+            self.contents.main = Some(label.clone());
+        }
+        self.contents.entries.push(VaultEntry { label, token });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, label: &str) -> Result<(), VaultError> {
+        let length = self.contents.entries.len();
+        self.contents.entries.retain(|e| e.label != label);
+        if self.contents.entries.len() == length {
+            return Err(VaultError::UnknownLabel(label.to_string()));
+        }
+        if self.contents.main.as_deref() == Some(label) {
+            self.contents.main = None;
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[VaultEntry] {
+        &self.contents.entries
+    }
+
+    pub fn main_label(&self) -> Option<&str> {
+        self.contents.main.as_deref()
+    }
+
+    pub fn main_token(&self) -> Option<SecretToken> {
+        let main = self.main_label()?;
+        self.contents
+            .entries
+            .iter()
+            .find(|e| e.label == main)
+            .map(VaultEntry::token)
+    }
+
+    /// Every token except the main one.
+    pub fn sub_tokens(&self) -> Vec<SecretToken> {
+        self.contents
+            .entries
+            .iter()
+            .filter(|e| Some(e.label.as_str()) != self.main_label())
+            .map(VaultEntry::token)
+            .collect()
+    }
+}
+
+/// Creates a file only its owner can read and write on Unix.
+fn create_private(path: &Path) -> std::io::Result<File> {
+    // A leftover file would keep its old permissions.
+    let _ = fs::remove_file(path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn make_cipher(
+    passphrase: &str,
+    salt: &[u8],
+    kdf: &KdfParams,
+) -> Result<ChaCha20Poly1305, VaultError> {
+    let config = argon2::Config {
+        variant: argon2::Variant::Argon2id,
+        version: argon2::Version::Version13,
+        mem_cost: kdf.mem_cost,
+        time_cost: kdf.time_cost,
+        lanes: kdf.lanes,
+        thread_mode: argon2::ThreadMode::Sequential,
+        hash_length: KEY_LENGTH,
+        ..argon2::Config::default()
+    };
+    let key = argon2::hash_raw(passphrase.as_bytes(), salt, &config)
+        .map_err(|_| VaultError::Malformed)?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Gets the vault passphrase from a key file if there is one, otherwise asks for it.
+pub fn read_passphrase(key_file: Option<&Path>) -> Result<String, VaultError> {
+    let passphrase = if let Some(key_file) = key_file {
+        fs::read_to_string(key_file)
+            .map(|k| k.trim().to_string())
+            .map_err(|_| VaultError::FailedReadingKeyFile(key_file.to_path_buf()))?
+    } else {
+        prompt_secret("Vault passphrase: ").map_err(|_| VaultError::NoPassphrase)?
+    };
+
+    if passphrase.is_empty() {
+        Err(VaultError::NoPassphrase)
+    } else {
+        Ok(passphrase)
+    }
+}
+
+/// Gets the passphrase for a new vault, asking twice so a typo can't lock you out.
+pub fn read_new_passphrase(key_file: Option<&Path>) -> Result<String, VaultError> {
+    let passphrase = read_passphrase(key_file)?;
+    if key_file.is_none() && atty::is(atty::Stream::Stdin) {
+        let confirmation =
+            prompt_secret("Repeat the passphrase: ").map_err(|_| VaultError::NoPassphrase)?;
+        if confirmation != passphrase {
+            return Err(VaultError::PassphraseMismatch);
+        }
+    }
+    Ok(passphrase)
+}
+
+/// Reads a secret without echoing it on a terminal, or a plain line from piped stdin.
+pub fn prompt_secret(prompt: &str) -> std::io::Result<String> {
+    if atty::is(atty::Stream::Stdin) {
+        rpassword::read_password_from_tty(Some(prompt))
+    } else {
+        let mut line = String::new();
+        stdin().read_line(&mut line)?;
+        Ok(line.trim().to_string())
+    }
+}
+
+pub enum VaultError {
+    FailedReading,
+    FailedReadingKeyFile(PathBuf),
+    FailedWriting,
+    Malformed,
+    WrongPassphrase,
+    NoPassphrase,
+    PassphraseMismatch,
+    UnsafeKdf,
+    DuplicateLabel(String),
+    UnknownLabel(String),
+}
+
+impl VaultError {
+    pub fn handle(&self) {
+        match self {
+            VaultError::FailedReading => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "I couldn't read your vault...");
+            }
+            VaultError::FailedReadingKeyFile(path) => {
+                log_error_and_exit!(
+                    "┐(¯ω¯;)┌",
+                    "I couldn't read the vault key file {}...",
+                    path.display()
+                );
+            }
+            VaultError::FailedWriting => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "I couldn't write your vault...");
+            }
+            VaultError::Malformed => {
+                log_error_and_exit!("(x_x)", "Your vault file is damaged.");
+            }
+            VaultError::WrongPassphrase => {
+                log_error_and_exit!("(＃`Д´)", "That passphrase doesn't unlock your vault.");
+            }
+            VaultError::NoPassphrase => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "I need a passphrase to unlock your vault.");
+            }
+            VaultError::PassphraseMismatch => {
+                log_error_and_exit!("(・へ・)", "The passphrases don't match, try again.");
+            }
+            VaultError::UnsafeKdf => {
+                pretty_error!(
+                    "(x_x)",
+                    "Your vault's key derivation settings are out of bounds."
+                );
+                log_error_and_exit!("->", "It wasn't written by RNS, or it was tampered with.");
+            }
+            VaultError::DuplicateLabel(label) => {
+                pretty_error!("┐(¯ω¯;)┌", "There already is a token labelled {}.", label);
+                log_error_and_exit!("->", "Remove it first if you want to replace it.");
+            }
+            VaultError::UnknownLabel(label) => {
+                log_error_and_exit!("┐(¯ω¯;)┌", "There's no token labelled {}.", label);
+            }
+        }
+    }
+}
@@ -1,13 +1,11 @@
-use crate::discord::Profile;
+use crate::discord::{HttpsClient, Profile};
+use crate::gift::GiftInfo;
 use crate::util::user_to_tag;
-use hyper::client::HttpConnector;
-use hyper::{Body, Client, Method, Request, StatusCode};
-use hyper_tls::HttpsConnector;
+use hyper::{Body, Method, Request, StatusCode};
 use serde_json::Value;
 use serenity::model::channel::{Embed, Message};
 
-type HttpsClient = Client<HttpsConnector<HttpConnector>>;
-
+/// A Discord webhook RNS reports successful snipes to.
 pub struct Webhook {
     pub url: String,
 }
@@ -22,8 +20,9 @@ impl Webhook {
         message: &Message,
         client: &HttpsClient,
         finder: &Profile,
+        gift: Option<&GiftInfo>,
     ) -> Result<(), ()> {
-        let payload = WebhookPayload::new(message, finder);
+        let payload = WebhookPayload::new(message, finder, gift);
         let request = Request::builder()
             .method(Method::POST)
             .uri(&self.url)
@@ -52,7 +51,7 @@ struct WebhookPayload {
 }
 
 impl WebhookPayload {
-    fn new(message: &Message, finder: &Profile) -> Self {
+    fn new(message: &Message, finder: &Profile, gift: Option<&GiftInfo>) -> Self {
         let embed = Embed::fake(|create| {
             create
                 .author(|a| a.icon_url(finder.face()).name(finder.to_string()))
@@ -80,7 +79,18 @@ impl WebhookPayload {
                         .text(format!("RNS {}", env!("CARGO_PKG_VERSION")))
                 })
                 .timestamp(chrono::Local::now().to_rfc3339())
-                .colour(5973197)
+                .colour(5973197);
+            if let Some(gift) = gift {
+                create.field("Gift:", gift.name(), true);
+                create.field("Uses:", format!("{}/{}", gift.uses, gift.max_uses), true);
+                if let Some(expires_at) = &gift.expires_at {
+                    create.field("Expires:", expires_at, true);
+                }
+                if let Some(gifter) = &gift.gifter {
+                    create.field("Bought by:", gifter.to_string(), true);
+                }
+            }
+            create
         });
         let embeds = vec![embed];
         WebhookPayload {
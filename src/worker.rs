@@ -0,0 +1,265 @@
+use crate::cache::LocationCache;
+use crate::config::Config;
+use crate::discord::{
+    get_gift_info, redeem_code_with_retries, GiftInfoError, HandlerInfo, Profile, Route,
+    RATE_LIMITS,
+};
+use crate::gift::{GiftInfo, GiftInfoLookup};
+use crate::logging::{LogBlock, Timeline};
+use crate::matcher::GiftMatch;
+use crate::redeem::RedeemOutcome;
+use crate::stats::ClaimTimings;
+use crate::util::user_to_tag;
+use crate::webhook::Webhook;
+use crate::{pretty_error, pretty_info, pretty_success, pretty_warn};
+use colored::*;
+use serenity::http::Http;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, MessageId};
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+/// The account that found a message, and what it takes to log codes on its behalf.
+#[derive(Clone)]
+pub struct Finder {
+    pub profile: Profile,
+    pub http: Arc<Http>,
+    pub location_cache: Arc<LocationCache>,
+}
+
+/// A gift code waiting to be redeemed, with everything needed to log the attempt.
+pub struct RedeemJob {
+    pub gift_match: GiftMatch,
+    pub message: Message,
+    pub finder: Finder,
+    pub config: Arc<Config>,
+    /// Timed phases from before the code was queued.
+    pub timeline: Timeline,
+}
+
+/// A message the gateway didn't send along, which has to be fetched before redeeming its codes.
+pub struct FetchJob {
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    /// The codes an edit added, or `None` to scan the whole message once it's fetched.
+    pub gift_matches: Option<Vec<GiftMatch>>,
+    pub finder: Finder,
+    pub config: Arc<Config>,
+    pub timeline: Timeline,
+}
+
+pub enum Job {
+    Redeem(RedeemJob),
+    Fetch(FetchJob),
+}
+
+/// The sending half of the redeem queue. Submitting never waits.
+#[derive(Clone)]
+pub struct RedeemQueue {
+    sender: UnboundedSender<Job>,
+}
+
+impl RedeemQueue {
+    pub fn submit(&self, job: RedeemJob) {
+        if self.sender.send(Job::Redeem(job)).is_err() {
+            pretty_error!("(x_x)", "The redeem workers are gone, dropping a code.");
+        }
+    }
+
+    /// Has a worker fetch the message, so event handlers never wait on a request.
+    pub fn fetch(&self, job: FetchJob) {
+        if self.sender.send(Job::Fetch(job)).is_err() {
+            pretty_error!("(x_x)", "The redeem workers are gone, dropping a message.");
+        }
+    }
+}
+
+/// Creates the redeem queue. Hand the receiver to `spawn_workers` once there's a `HandlerInfo`.
+pub fn redeem_queue() -> (RedeemQueue, UnboundedReceiver<Job>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (RedeemQueue { sender }, receiver)
+}
+
+/// Starts `concurrency` workers that redeem queued codes.
+pub fn spawn_workers(receiver: UnboundedReceiver<Job>, info: Arc<HandlerInfo>, concurrency: usize) {
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..concurrency.max(1) {
+        let receiver = receiver.clone();
+        let info = info.clone();
+        tokio::spawn(async move {
+            loop {
+                let job = receiver.lock().await.recv().await;
+                match job {
+                    Some(Job::Redeem(job)) => redeem(job, &info).await,
+                    Some(Job::Fetch(job)) => fetch(job, &info).await,
+                    None => break,
+                }
+            }
+        });
+    }
+}
+
+async fn redeem(job: RedeemJob, info: &HandlerInfo) {
+    let gift_code = &job.gift_match.code;
+    let config = &job.config;
+
+    let mut timeline = job.timeline.clone();
+    timeline.mark("queue");
+    let mut log = LogBlock::with_timeline(&job.finder.profile, timeline);
+    pretty_info!(
+        log: log,
+        "(°■°)!",
+        "Claiming code: {}! (matched {}, score {:.3})",
+        gift_code,
+        job.gift_match.pattern,
+        job.gift_match.score
+    );
+
+    let lookup = config.gift_info_lookup();
+    let mut gift = None;
+    let mut skip_outcome = None;
+    if lookup == GiftInfoLookup::BeforeRedeem {
+        match look_up_gift(&job, info, &mut log).await {
+            Ok(found) => {
+                if found.is_used_up() {
+                    skip_outcome = Some(RedeemOutcome::SkippedUsedUp);
+                }
+                gift = Some(found);
+            }
+            Err(GiftInfoError::UnknownCode) => skip_outcome = Some(RedeemOutcome::SkippedUnknown),
+            Err(_) => {}
+        }
+    }
+
+    let outcome = if let Some(outcome) = skip_outcome {
+        outcome
+    } else {
+        redeem_code_with_retries(gift_code, info.client(), config, &mut log).await
+    };
+    outcome.log(&mut log);
+    if let Some(budget) = RATE_LIMITS.budget(&config.main_token(), Route::Redeem) {
+        pretty_info!(
+            log: log,
+            "(o·ω·o)",
+            "{} of {} redeem request(s) left, resetting in {}ms.",
+            budget.remaining,
+            budget.limit.map_or_else(|| "?".to_string(), |l| l.to_string()),
+            budget.resets_in.as_millis()
+        );
+    }
+    if let RedeemOutcome::RateLimited { .. } = outcome {
+        // The code was never really tried, so it's fair game if someone posts it again.
+        info.forget_code(gift_code).await;
+    } else {
+        info.record_outcome(gift_code, outcome.label()).await;
+    }
+
+    let worth_looking_up = matches!(
+        outcome,
+        RedeemOutcome::Claimed | RedeemOutcome::AlreadyRedeemed
+    );
+    if lookup == GiftInfoLookup::AfterRedeem && worth_looking_up {
+        gift = look_up_gift(&job, info, &mut log).await.ok();
+    }
+    if outcome.is_claimed() {
+        on_success(&job, info, gift.as_ref(), &mut log).await;
+    }
+    log.freeze_time();
+    info.record_timings(&ClaimTimings::new(
+        gift_code,
+        outcome.label(),
+        log.timeline(),
+    ));
+
+    let location = timeout(
+        config.redeem_timeout(),
+        job.finder.location_cache.get_and_cache_location(
+            job.message.channel_id,
+            job.message.guild_id,
+            &job.finder.http,
+        ),
+    )
+    .await
+    .unwrap_or(Err(()));
+    log.send(location, user_to_tag(&job.message.author));
+}
+
+/// Fetches a message, then queues the codes in it like the event handlers do.
+async fn fetch(job: FetchJob, info: &HandlerInfo) {
+    let mut timeline = job.timeline;
+    let fetched = timeout(
+        job.config.redeem_timeout(),
+        job.channel_id.message(&*job.finder.http, job.message_id),
+    )
+    .await;
+    let message = match fetched {
+        Ok(Ok(message)) => message,
+        _ => return,
+    };
+
+    let gift_matches = match job.gift_matches {
+        Some(gift_matches) => {
+            timeline.mark("fetch message");
+            gift_matches
+        }
+        None => {
+            timeline.mark("fetch reply");
+            let gift_matches = job.config.matcher().score_message(&message);
+            timeline.mark("detect");
+            gift_matches
+        }
+    };
+    info.snipe_all(&message, gift_matches, &job.config, &job.finder, timeline)
+        .await;
+}
+
+/// Fetches what the code is for, then adds it to the log and the seen codes history.
+async fn look_up_gift(
+    job: &RedeemJob,
+    info: &HandlerInfo,
+    log: &mut LogBlock<'_>,
+) -> Result<GiftInfo, GiftInfoError> {
+    let gift_code = &job.gift_match.code;
+    let result = get_gift_info(gift_code, info.client(), &job.config).await;
+    log.timeline().mark("gift info");
+
+    match &result {
+        Ok(gift) => {
+            gift.log(log);
+            info.record_gift_info(gift_code, gift).await;
+        }
+        Err(GiftInfoError::UnknownCode) => {
+            pretty_warn!(log: log, "(╥ω╥)", "Discord doesn't know this gift code.");
+        }
+        Err(GiftInfoError::RateLimited) => {
+            pretty_warn!(log: log, "(x_x)", "Looking up the gift was rate-limited...");
+        }
+        Err(GiftInfoError::Other) => {
+            pretty_warn!(log: log, "┐(¯ω¯;)┌", "Couldn't look up what the gift is.");
+        }
+    }
+    result
+}
+
+async fn on_success(
+    job: &RedeemJob,
+    info: &HandlerInfo,
+    gift: Option<&GiftInfo>,
+    log: &mut LogBlock<'_>,
+) {
+    if let Some(webhook_url) = job.config.webhook() {
+        pretty_success!(log: log, "(o·ω·o)", "Sending webhook message!");
+        let webhook = Webhook::new(webhook_url);
+        let sent = timeout(
+            job.config.redeem_timeout(),
+            webhook.send(&job.message, info.client(), &job.finder.profile, gift),
+        )
+        .await;
+        if !matches!(sent, Ok(Ok(_))) {
+            pretty_error!(log: log, "┐(¯ω¯;)┌", "Failed sending webhook message");
+        }
+        log.timeline().mark("webhook");
+    }
+}
@@ -0,0 +1,106 @@
+use futures::future::FutureExt;
+use rust_nitro_sniper::clients::{ClientPool, Connection, Connector, Shutdown};
+use rust_nitro_sniper::config::Config;
+use rust_nitro_sniper::discord::HandlerInfo;
+use rust_nitro_sniper::store::MemoryStore;
+use rust_nitro_sniper::token::SecretToken;
+use rust_nitro_sniper::{connection, worker};
+use serenity::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task;
+
+const GUILDS_PER_ACCOUNT: usize = 3;
+
+struct FakeShutdown(Arc<Notify>);
+
+#[async_trait]
+impl Shutdown for FakeShutdown {
+    async fn shutdown(&self) {
+        self.0.notify();
+    }
+}
+
+/// Connects after yielding once, and stays connected until shut down.
+struct FakeConnector;
+
+#[async_trait]
+impl Connector for FakeConnector {
+    async fn connect(&self, token: &SecretToken, info: Arc<HandlerInfo>) -> Option<Connection> {
+        // Give anything waiting on the pool a chance to run while we connect.
+        task::yield_now().await;
+        info.account_connected(token, GUILDS_PER_ACCOUNT);
+
+        let stopped = Arc::new(Notify::new());
+        let run_stopped = stopped.clone();
+        Some(Connection {
+            run: async move { run_stopped.notified().await }
+                .map(|_| true)
+                .boxed(),
+            shutdown: Box::new(FakeShutdown(stopped)),
+        })
+    }
+}
+
+fn handler_info() -> Arc<HandlerInfo> {
+    let (queue, _) = worker::redeem_queue();
+    Arc::new(HandlerInfo::new(
+        connection::build_client(false),
+        Config::default(),
+        Box::new(MemoryStore::new(16, Duration::from_secs(60))),
+        queue,
+        2,
+    ))
+}
+
+/// Gives every other task on the test's single-threaded runtime plenty of turns to run.
+async fn settle() {
+    for _ in 0..16 {
+        task::yield_now().await;
+    }
+}
+
+fn tokens(values: &[&str]) -> Vec<SecretToken> {
+    values.iter().map(|v| SecretToken::new(*v)).collect()
+}
+
+#[tokio::test]
+async fn swapping_every_token_never_empties_the_pool() {
+    let info = handler_info();
+    let pool = ClientPool::with_connector(info.clone(), Box::new(FakeConnector));
+    pool.sync(tokens(&["old-a", "old-b"])).await;
+
+    let waiting_pool = pool.clone();
+    let mut emptied = tokio::spawn(async move { waiting_pool.wait_until_empty().await });
+
+    pool.sync(tokens(&["new-a", "new-b"])).await;
+    settle().await;
+    assert!(
+        (&mut emptied).now_or_never().is_none(),
+        "the pool looked empty during the reload"
+    );
+
+    let mut running = pool.tokens().await;
+    running.sort();
+    assert_eq!(running, tokens(&["new-a", "new-b"]));
+
+    pool.sync(Vec::new()).await;
+    settle().await;
+    assert!(emptied.now_or_never().is_some());
+}
+
+#[tokio::test]
+async fn stopped_accounts_are_no_longer_counted() {
+    let info = handler_info();
+    let pool = ClientPool::with_connector(info.clone(), Box::new(FakeConnector));
+
+    pool.sync(tokens(&["a", "b"])).await;
+    assert_eq!(info.connected(), (2, 2 * GUILDS_PER_ACCOUNT));
+
+    pool.sync(tokens(&["b"])).await;
+    assert_eq!(info.connected(), (1, GUILDS_PER_ACCOUNT));
+
+    pool.sync(tokens(&["c", "d"])).await;
+    assert_eq!(info.connected(), (2, 2 * GUILDS_PER_ACCOUNT));
+}
@@ -0,0 +1,67 @@
+//! A local stand-in for Discord's API, for tests that make real requests.
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use rust_nitro_sniper::config::Config;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Serves HTTP/1.1 on a free loopback port, answering every request with `respond`.
+pub fn serve<F>(respond: F) -> SocketAddr
+where
+    F: Fn(Request<Body>) -> Response<Body> + Send + Sync + 'static,
+{
+    serve_version(false, respond)
+}
+
+/// Like `serve`, but speaks only HTTP/2, without TLS.
+#[allow(dead_code)]
+pub fn serve_http2<F>(respond: F) -> SocketAddr
+where
+    F: Fn(Request<Body>) -> Response<Body> + Send + Sync + 'static,
+{
+    serve_version(true, respond)
+}
+
+fn serve_version<F>(http2: bool, respond: F) -> SocketAddr
+where
+    F: Fn(Request<Body>) -> Response<Body> + Send + Sync + 'static,
+{
+    let respond = Arc::new(respond);
+    let make_service = make_service_fn(move |_| {
+        let respond = respond.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = respond(request);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+
+    let builder = Server::bind(&([127, 0, 0, 1], 0).into());
+    // Turning either one off resets the other, so only turn one on.
+    let builder = if http2 {
+        builder.http2_only(true)
+    } else {
+        builder.http1_only(true)
+    };
+    let server = builder.serve(make_service);
+    let address = server.local_addr();
+    tokio::spawn(server);
+    address
+}
+
+/// A config that sends every request to the server at `address`.
+pub fn config_for(address: SocketAddr, extra: serde_json::Value) -> Config {
+    let mut config = serde_json::json!({
+        "main_token": "main-token",
+        "snipe_on_main_token": true,
+        "guild_blacklist": [],
+        "api_base": format!("http://{}/api", address),
+    });
+    for (key, value) in extra.as_object().unwrap() {
+        config[key] = value.clone();
+    }
+    serde_json::from_value(config).unwrap()
+}
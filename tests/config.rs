@@ -0,0 +1,160 @@
+use rust_nitro_sniper::config::{
+    try_read_config, Config, ConfigFormat, ConfigProblem, ConfigReadError,
+};
+use std::fs;
+
+const MAIN_TOKEN: &str = "NzY5MDg4NTY0NjQ3NzA3NjQ4.X5KYdw.abcdefghijklmnopqrstuvwxyz0";
+const SUB_TOKEN: &str = "ODAzNTExMTAyMjQ2Nzg5MTI0.YA1b2c.zyxwvutsrqponmlkjihgfedcba9";
+
+fn read(name: &str, contents: &str) -> Result<Config, ConfigReadError> {
+    let path = std::env::temp_dir().join(format!(
+        "rns-test-config-{}-{}.json",
+        name,
+        std::process::id()
+    ));
+    fs::write(&path, contents).unwrap();
+    let result = try_read_config(&path);
+    fs::remove_file(&path).unwrap();
+    result
+}
+
+/// Reads a JSON config, returning the problems found in it.
+fn problems_in(name: &str, contents: &str) -> Vec<ConfigProblem> {
+    match read(name, contents) {
+        Ok(_) => Vec::new(),
+        Err(ConfigReadError::InvalidConfig(problems)) => problems,
+        Err(e) => panic!("expected config problems, got: {}", e),
+    }
+}
+
+#[test]
+fn a_valid_config_has_no_problems() {
+    let config = format!(
+        r#"{{
+            "main_token": "{}",
+            "snipe_on_main_token": true,
+            "sub_tokens": ["{}"],
+            "webhook": "https://discord.com/api/webhooks/123456789012345678/abc-DEF_123",
+            "guild_blacklist": [123456789012345678]
+        }}"#,
+        MAIN_TOKEN, SUB_TOKEN
+    );
+    assert!(problems_in("valid", &config).is_empty());
+}
+
+#[test]
+fn every_problem_is_reported_with_its_path() {
+    let config = format!(
+        r#"{{
+            "main_token": "not a token",
+            "snipe_on_main_token": true,
+            "sub_tokens": ["{}", "also not a token", "{}"],
+            "webhook": "https://example.com/hook",
+            "guild_blacklist": [5, 123456789012345678, 123456789012345678],
+            "seen_codes_capacity": 0,
+            "redeem_concurrency": 0,
+            "score_threshold": 2,
+            "api_base": "http://discord.example.com/api",
+            "colour": "blue"
+        }}"#,
+        SUB_TOKEN, SUB_TOKEN
+    );
+    let problems = problems_in("many", &config);
+
+    let paths = problems.iter().map(|p| p.path.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            "$.colour",
+            "$.main_token",
+            "$.sub_tokens[1]",
+            "$.sub_tokens[2]",
+            "$.webhook",
+            "$.guild_blacklist[0]",
+            "$.guild_blacklist[2]",
+            "$.seen_codes_capacity",
+            "$.redeem_concurrency",
+            "$.score_threshold",
+            "$.api_base",
+        ]
+    );
+    assert!(problems
+        .iter()
+        .all(|p| !p.problem.is_empty() && !p.fix.is_empty()));
+}
+
+#[test]
+fn a_seen_codes_capacity_of_zero_is_refused() {
+    let config = format!(
+        r#"{{"main_token": "{}", "snipe_on_main_token": true, "guild_blacklist": [], "seen_codes_capacity": 0}}"#,
+        MAIN_TOKEN
+    );
+    let problems = problems_in("capacity", &config);
+
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].path, "$.seen_codes_capacity");
+    assert!(problems[0].fix.contains("1 or more"));
+}
+
+#[test]
+fn templates_match_the_default_config() {
+    let default = serde_json::to_value(Config::default()).unwrap();
+
+    for format in &[ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+        let raw = format.parse(&format.template()).unwrap();
+        for key in raw.as_object().unwrap().keys() {
+            assert!(
+                default.get(key).is_some(),
+                "the {:?} template has an unknown key {}",
+                format,
+                key
+            );
+        }
+
+        let config = serde_json::from_value::<Config>(raw).unwrap();
+        assert_eq!(
+            serde_json::to_value(config).unwrap(),
+            default,
+            "the {:?} template doesn't match the default config",
+            format
+        );
+    }
+}
+
+#[test]
+fn configured_gift_patterns_replace_the_builtin_ones() {
+    let config = format!(
+        r#"{{
+            "main_token": "{}",
+            "snipe_on_main_token": true,
+            "guild_blacklist": [],
+            "gift_patterns": [
+                {{
+                    "name": "promos",
+                    "regex": "(promos)\\.discord\\.gg/([a-zA-Z0-9]+)",
+                    "group": 2,
+                    "min_length": 10,
+                    "max_length": 12
+                }}
+            ],
+            "scorer": "accept_all"
+        }}"#,
+        MAIN_TOKEN
+    );
+    let config = read("patterns", &config).unwrap_or_else(|e| panic!("{}", e));
+
+    let text = "promos.discord.gg/abcdefghi promos.discord.gg/abcdefghij \
+                promos.discord.gg/abcdefghijkl promos.discord.gg/abcdefghijklm \
+                discord.gift/abcdefghijklmnop";
+    let found = config.matcher().find_gift_codes(text);
+    let found = found
+        .iter()
+        .map(|m| (m.code.as_str(), m.pattern.as_str()))
+        .collect::<Vec<_>>();
+
+    // Codes outside the length rule are skipped, and the built-in patterns are gone.
+    assert_eq!(
+        found,
+        vec![("abcdefghij", "promos"), ("abcdefghijkl", "promos")]
+    );
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use hyper::{Body, Request, Response, Version};
+use rust_nitro_sniper::connection::{build_client, connect_client};
+use rust_nitro_sniper::discord::HttpsClient;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+/// Serves HTTP/2 or HTTP/1.1, remembering the HTTP version of every request.
+fn recording(http2: bool) -> (SocketAddr, Arc<Mutex<Vec<Version>>>) {
+    let versions = Arc::new(Mutex::new(Vec::new()));
+    let recorded = versions.clone();
+    let respond = move |request: Request<Body>| {
+        recorded.lock().unwrap().push(request.version());
+        Response::new(Body::from("{}"))
+    };
+    let address = if http2 {
+        common::serve_http2(respond)
+    } else {
+        common::serve(respond)
+    };
+    (address, versions)
+}
+
+async fn get(client: &HttpsClient, address: SocketAddr) -> bool {
+    let uri = format!("http://{}/api/v8/gateway", address)
+        .parse()
+        .unwrap();
+    client.get(uri).await.is_ok()
+}
+
+#[tokio::test]
+async fn http2_falls_back_to_http1_when_the_server_only_speaks_http1() {
+    let (address, versions) = recording(false);
+    let config = common::config_for(address, serde_json::json!({"http2": true}));
+
+    // An HTTP/2 only client can't talk to this server at all.
+    assert!(!get(&build_client(true), address).await);
+
+    let client = connect_client(&config).await;
+    versions.lock().unwrap().clear();
+    assert!(get(&client, address).await);
+    assert_eq!(*versions.lock().unwrap(), vec![Version::HTTP_11]);
+}
+
+#[tokio::test]
+async fn http2_is_used_when_the_server_speaks_it() {
+    let (address, versions) = recording(true);
+    let config = common::config_for(address, serde_json::json!({"http2": true}));
+
+    let client = connect_client(&config).await;
+    versions.lock().unwrap().clear();
+    assert!(get(&client, address).await);
+    assert_eq!(*versions.lock().unwrap(), vec![Version::HTTP_2]);
+}
+
+#[tokio::test]
+async fn http1_is_used_when_http2_is_off() {
+    let (address, versions) = recording(false);
+    let config = common::config_for(address, serde_json::json!({}));
+
+    let client = connect_client(&config).await;
+    assert!(get(&client, address).await);
+    assert_eq!(*versions.lock().unwrap(), vec![Version::HTTP_11]);
+}
@@ -0,0 +1,110 @@
+#[macro_use]
+extern crate lazy_static;
+
+use rust_nitro_sniper::config::Config;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+const VARIABLES: [&str; 6] = [
+    "RNS_MAIN_TOKEN",
+    "RNS_MAIN_TOKEN_FILE",
+    "RNS_SUB_TOKENS",
+    "RNS_SUB_TOKENS_FILE",
+    "RNS_WEBHOOK",
+    "RNS_WEBHOOK_FILE",
+];
+
+lazy_static! {
+    /// The environment is shared by every test in this file, so they take turns.
+    static ref ENVIRONMENT: Mutex<()> = Mutex::new(());
+}
+
+fn clean_environment() -> MutexGuard<'static, ()> {
+    let guard = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+    for variable in &VARIABLES {
+        env::remove_var(variable);
+    }
+    guard
+}
+
+fn secrets_file(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("rns-test-{}-{}", name, std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn overridden() -> Config {
+    let mut config = Config::default();
+    config
+        .apply_env_overrides()
+        .unwrap_or_else(|e| panic!("couldn't apply the overrides: {}", e));
+    config
+}
+
+fn main_token() -> String {
+    overridden().main_token().expose().to_string()
+}
+
+#[test]
+fn the_variable_wins_over_its_file_which_wins_over_the_config() {
+    let _environment = clean_environment();
+    let file = secrets_file("main-token", "from-file");
+    assert_eq!(main_token(), "YOUR_TOKEN_HERE");
+
+    env::set_var("RNS_MAIN_TOKEN_FILE", &file);
+    assert_eq!(main_token(), "from-file");
+
+    env::set_var("RNS_MAIN_TOKEN", "from-variable");
+    assert_eq!(main_token(), "from-variable");
+
+    env::remove_var("RNS_MAIN_TOKEN_FILE");
+    fs::remove_file(&file).unwrap();
+    assert_eq!(main_token(), "from-variable");
+}
+
+#[test]
+fn empty_values_count_as_unset() {
+    let _environment = clean_environment();
+    let file = secrets_file("empty-main-token", "from-file\n");
+
+    env::set_var("RNS_MAIN_TOKEN", "");
+    assert_eq!(main_token(), "YOUR_TOKEN_HERE");
+
+    env::set_var("RNS_MAIN_TOKEN_FILE", &file);
+    assert_eq!(main_token(), "from-file");
+
+    let empty = secrets_file("empty-webhook", "\n");
+    env::set_var("RNS_WEBHOOK_FILE", &empty);
+    assert_eq!(overridden().webhook(), None);
+
+    fs::remove_file(&file).unwrap();
+    fs::remove_file(&empty).unwrap();
+}
+
+#[test]
+fn secrets_files_are_trimmed() {
+    let _environment = clean_environment();
+    let webhook = "https://discord.com/api/webhooks/123456789012345678/abc";
+    let webhook_file = secrets_file("webhook", &format!("{}\n", webhook));
+    let tokens_file = secrets_file("sub-tokens", "first\nsecond,third\n\n");
+    env::set_var("RNS_WEBHOOK_FILE", &webhook_file);
+    env::set_var("RNS_SUB_TOKENS_FILE", &tokens_file);
+
+    let config = overridden();
+    fs::remove_file(&webhook_file).unwrap();
+    fs::remove_file(&tokens_file).unwrap();
+
+    assert_eq!(config.webhook().as_deref(), Some(webhook));
+    let tokens = config.get_all_sniping_tokens();
+    let sub_tokens = tokens[1..].iter().map(|t| t.expose()).collect::<Vec<_>>();
+    assert_eq!(sub_tokens, vec!["first", "second", "third"]);
+}
+
+#[test]
+fn a_missing_secrets_file_is_an_error() {
+    let _environment = clean_environment();
+    env::set_var("RNS_MAIN_TOKEN_FILE", "/nonexistent/rns/main_token");
+    assert!(Config::default().apply_env_overrides().is_err());
+}
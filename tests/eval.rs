@@ -0,0 +1,89 @@
+use rust_nitro_sniper::eval::{evaluate, read_corpus, Confusion, Label, LabelledMessage};
+use rust_nitro_sniper::matcher::builtin_patterns;
+use rust_nitro_sniper::scorer::ScorerKind;
+use std::fs;
+
+const CORPUS: &str = r#"{"content": "discord.gift/Ab3dEf6hIj9kLm2n", "label": "real_code"}
+
+{"content": "nothing to see here", "label": "not_a_code"}
+
+{"content": "discord.gift/aaaaaaaaaaaaaaaa", "label": "not_a_code"}
+{"content": "the code is Ab3dEf6hIj9kLm2n", "label": "real_code"}
+"#;
+
+fn message(content: &str, label: Label, line: usize) -> LabelledMessage {
+    LabelledMessage {
+        content: content.to_string(),
+        label,
+        line,
+    }
+}
+
+#[test]
+fn precision_and_recall() {
+    let confusion = Confusion {
+        true_positives: 3,
+        false_positives: 1,
+        true_negatives: 5,
+        false_negatives: 2,
+    };
+    assert_eq!(confusion.total(), 11);
+    assert_eq!(confusion.precision(), Some(0.75));
+    assert_eq!(confusion.recall(), Some(0.6));
+}
+
+#[test]
+fn precision_and_recall_without_positives() {
+    let mut confusion = Confusion::default();
+    assert_eq!(confusion.precision(), None);
+    assert_eq!(confusion.recall(), None);
+
+    confusion.add(false, Label::NotACode);
+    assert_eq!(confusion.precision(), None);
+    assert_eq!(confusion.recall(), None);
+
+    confusion.add(false, Label::RealCode);
+    assert_eq!(confusion.precision(), None);
+    assert_eq!(confusion.recall(), Some(0.0));
+}
+
+#[test]
+fn evaluate_counts_every_outcome() {
+    let corpus = vec![
+        message("discord.gift/Ab3dEf6hIj9kLm2n", Label::RealCode, 1),
+        message("nothing to see here", Label::NotACode, 2),
+        message("discord.gift/aaaaaaaaaaaaaaaa", Label::NotACode, 3),
+        message("the code is Ab3dEf6hIj9kLm2n", Label::RealCode, 4),
+    ];
+    let evaluation = evaluate(&corpus, builtin_patterns(), ScorerKind::AcceptAll, 0.0).unwrap();
+
+    let overall = evaluation.overall;
+    assert_eq!(overall.true_positives, 1);
+    assert_eq!(overall.false_positives, 1);
+    assert_eq!(overall.true_negatives, 1);
+    assert_eq!(overall.false_negatives, 1);
+
+    // Only the messages a pattern found a candidate in count towards that pattern.
+    let (name, per_pattern) = &evaluation.per_pattern[0];
+    assert_eq!(name, "discord.gift");
+    assert_eq!(per_pattern.total(), 2);
+
+    assert_eq!(evaluation.false_positives.len(), 1);
+    assert_eq!(evaluation.false_positives[0].line, 3);
+    assert_eq!(evaluation.false_negatives.len(), 1);
+    assert_eq!(evaluation.false_negatives[0].line, 4);
+    assert!(evaluation.false_negatives[0].candidates.is_empty());
+}
+
+#[test]
+fn mistakes_point_at_the_line_in_the_file() {
+    let path = std::env::temp_dir().join(format!("rns-test-corpus-{}.jsonl", std::process::id()));
+    fs::write(&path, CORPUS).unwrap();
+    let corpus = read_corpus(&path).unwrap_or_else(|_| panic!("couldn't read the corpus"));
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(corpus.len(), 4);
+    let evaluation = evaluate(&corpus, builtin_patterns(), ScorerKind::AcceptAll, 0.0).unwrap();
+    assert_eq!(evaluation.false_positives[0].line, 5);
+    assert_eq!(evaluation.false_negatives[0].line, 6);
+}
@@ -0,0 +1,80 @@
+use rust_nitro_sniper::gift::{GiftInfo, Gifter};
+
+const SAMPLE_RESPONSE: &str = r#"{
+    "code": "Ab3dEf6hIj9kLm2n",
+    "sku_id": "521847234246082599",
+    "application_id": "521842831262875670",
+    "uses": 0,
+    "max_uses": 1,
+    "redeemed": false,
+    "expires_at": "2026-11-17T12:00:00+00:00",
+    "store_listing": {
+        "id": "521847234246082599",
+        "sku": {"id": "521847234246082599", "name": "Nitro", "type": 5}
+    },
+    "subscription_plan": {
+        "id": "511651880837840896",
+        "name": "Nitro Monthly",
+        "interval": 1,
+        "interval_count": 1
+    },
+    "user": {"id": "80351110224678912", "username": "nelly", "discriminator": "1337", "avatar": null}
+}"#;
+
+fn sample() -> GiftInfo {
+    serde_json::from_str(SAMPLE_RESPONSE).unwrap()
+}
+
+fn gifter(username: &str, discriminator: &str) -> Gifter {
+    Gifter {
+        username: username.to_string(),
+        discriminator: discriminator.to_string(),
+    }
+}
+
+#[test]
+fn sample_response_is_read() {
+    let gift = sample();
+    assert_eq!(gift.code, "Ab3dEf6hIj9kLm2n");
+    assert_eq!(gift.name(), "Nitro Monthly");
+    assert_eq!((gift.uses, gift.max_uses), (0, 1));
+    assert_eq!(
+        gift.expires_at.as_deref(),
+        Some("2026-11-17T12:00:00+00:00")
+    );
+    assert_eq!(gift.gifter.unwrap().to_string(), "nelly#1337");
+}
+
+#[test]
+fn missing_fields_fall_back() {
+    let gift: GiftInfo = serde_json::from_str(r#"{"code": "Ab3dEf6hIj9kLm2n"}"#).unwrap();
+    assert_eq!(gift.name(), "Unknown gift");
+    assert!(gift.gifter.is_none());
+    assert!(!gift.is_used_up());
+}
+
+#[test]
+fn used_up_gifts() {
+    let mut gift = sample();
+    assert!(!gift.is_used_up());
+
+    gift.uses = 1;
+    assert!(gift.is_used_up());
+
+    gift.uses = 0;
+    gift.redeemed = true;
+    assert!(gift.is_used_up());
+
+    // No limit on the uses.
+    gift.redeemed = false;
+    gift.max_uses = 0;
+    gift.uses = 5;
+    assert!(!gift.is_used_up());
+}
+
+#[test]
+fn gifters_are_shown_by_tag_or_username() {
+    assert_eq!(gifter("nelly", "1337").to_string(), "nelly#1337");
+    assert_eq!(gifter("nelly", "7").to_string(), "nelly#0007");
+    assert_eq!(gifter("nelly", "0").to_string(), "nelly");
+}
@@ -0,0 +1,216 @@
+use proptest::prelude::*;
+use rust_nitro_sniper::matcher::{builtin_patterns, sanitize_markdown, GiftPattern, Matcher};
+use rust_nitro_sniper::scorer::{AcceptAllScorer, ScorerKind};
+use serde_json::json;
+use serenity::model::event::MessageUpdateEvent;
+
+const MARKDOWN_CHARS: [char; 5] = ['*', '_', '`', '~', '|'];
+
+fn gift_code() -> impl Strategy<Value = String> {
+    "[a-zA-Z0-9]{16,24}"
+}
+
+fn gift_link() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("discord.gift/"),
+        Just("discord.com/gifts/"),
+        Just("discordapp.com/gifts/"),
+    ]
+    .prop_map(String::from)
+}
+
+/// Inserts markdown characters at the given positions, counted in chars.
+fn with_markdown(text: &str, insertions: &[(usize, usize)]) -> String {
+    let mut chars: Vec<char> = text.chars().collect();
+    for &(position, markdown) in insertions {
+        let position = position % (chars.len() + 1);
+        chars.insert(position, MARKDOWN_CHARS[markdown % MARKDOWN_CHARS.len()]);
+    }
+    chars.into_iter().collect()
+}
+
+proptest! {
+    #[test]
+    fn matcher_never_panics(text in "\\PC*") {
+        let matcher = Matcher::default();
+        matcher.find_gift_codes(&text);
+    }
+
+    #[test]
+    fn scores_are_between_zero_and_one(code in "\\PC*") {
+        for kind in &ScorerKind::ALL {
+            let score = kind.build().score(&code);
+            prop_assert!((0.0..=1.0).contains(&score), "{} scored {}", kind, score);
+        }
+    }
+
+    #[test]
+    fn scores_of_candidates_are_between_zero_and_one(
+        link in gift_link(),
+        code in gift_code(),
+    ) {
+        let text = format!("{}{}", link, code);
+        for gift_match in Matcher::default().score_gift_codes_in(std::iter::once(text.as_str())) {
+            prop_assert!((0.0..=1.0).contains(&gift_match.score));
+        }
+    }
+
+    #[test]
+    fn sanitizing_is_idempotent(text in "\\PC*") {
+        let once = sanitize_markdown(&text);
+        prop_assert_eq!(sanitize_markdown(&once), once);
+    }
+
+    #[test]
+    fn extraction_ignores_extra_markdown(
+        prefix in "\\PC*",
+        link in gift_link(),
+        code in gift_code(),
+        suffix in "\\PC*",
+        insertions in prop::collection::vec((any::<usize>(), any::<usize>()), 0..16),
+    ) {
+        let matcher = Matcher::default();
+        let text = format!("{} {}{} {}", prefix, link, code, suffix);
+        let decorated = with_markdown(&text, &insertions);
+        prop_assert_eq!(matcher.find_gift_codes(&text), matcher.find_gift_codes(&decorated));
+    }
+
+    #[test]
+    fn embedded_codes_are_found(
+        prefix in "\\PC*",
+        link in gift_link(),
+        spaces in " {0,3}",
+        code in gift_code(),
+        suffix in "\\PC*",
+    ) {
+        let matcher = Matcher::default().with_scorer(Box::new(AcceptAllScorer), 0.0);
+        let text = format!("{} {}{}{} {}", prefix, link, spaces, code, suffix);
+        let found = matcher.find_gift_codes(&text);
+        prop_assert!(found.iter().any(|m| m.code == code), "{:?} not found in {:?}", code, text);
+    }
+}
+
+fn found(matcher: &Matcher, text: &str) -> Vec<(String, String)> {
+    matcher
+        .find_gift_codes(text)
+        .into_iter()
+        .map(|m| (m.code, m.pattern))
+        .collect()
+}
+
+fn pair(code: &str, pattern: &str) -> (String, String) {
+    (code.to_string(), pattern.to_string())
+}
+
+#[test]
+fn every_distinct_code_is_found_in_message_order() {
+    let matcher = Matcher::default().with_scorer(Box::new(AcceptAllScorer), 0.0);
+    let text = "discordapp.com/gifts/CCCCCCCCCCCCCCCC first, then discord.gift/AAAAAAAAAAAAAAAA, \
+                discord.com/gifts/BBBBBBBBBBBBBBBB and discord.gift/AAAAAAAAAAAAAAAA again";
+
+    assert_eq!(
+        found(&matcher, text),
+        vec![
+            pair("CCCCCCCCCCCCCCCC", "discordapp.com/gifts"),
+            pair("AAAAAAAAAAAAAAAA", "discord.gift"),
+            pair("BBBBBBBBBBBBBBBB", "discord.com/gifts"),
+        ]
+    );
+}
+
+#[test]
+fn codes_matched_by_several_patterns_are_found_once() {
+    let mut patterns = builtin_patterns();
+    patterns.push(GiftPattern {
+        name: "any gift link".to_string(),
+        regex: r"gifts?/([a-zA-Z0-9]{16,24})".to_string(),
+        group: 1,
+        min_length: 16,
+        max_length: 24,
+    });
+    let matcher = Matcher::new(patterns)
+        .unwrap()
+        .with_scorer(Box::new(AcceptAllScorer), 0.0);
+    let text = "discord.gift/AAAAAAAAAAAAAAAA example.com/gift/BBBBBBBBBBBBBBBB";
+
+    // Both patterns match the first link, and the one listed first names it.
+    assert_eq!(
+        found(&matcher, text),
+        vec![
+            pair("AAAAAAAAAAAAAAAA", "discord.gift"),
+            pair("BBBBBBBBBBBBBBBB", "any gift link"),
+        ]
+    );
+}
+
+fn edit(update: serde_json::Value) -> MessageUpdateEvent {
+    let mut event = json!({"id": 1, "channel_id": 2});
+    event
+        .as_object_mut()
+        .unwrap()
+        .extend(update.as_object().unwrap().clone());
+    serde_json::from_value(event).unwrap()
+}
+
+fn codes_in_edit(update: serde_json::Value) -> Vec<String> {
+    Matcher::default()
+        .with_scorer(Box::new(AcceptAllScorer), 0.0)
+        .score_edit(&edit(update))
+        .into_iter()
+        .map(|m| m.code)
+        .collect()
+}
+
+#[test]
+fn codes_in_every_part_of_an_embed_are_found() {
+    let codes = codes_in_edit(json!({
+        "embeds": [{
+            "type": "rich",
+            "title": "discord.gift/TitleTitleTitle01",
+            "description": "Free nitro: discord.gift/DescriptionDesc01",
+            "fields": [
+                {"name": "discord.gift/FieldNameField01", "value": "nothing here"},
+                {"name": "Link", "value": "discord.gift/FieldValueValue01"},
+            ],
+            "footer": {"text": "discord.gift/FooterFooterFoo01"},
+        }],
+    }));
+
+    assert_eq!(
+        codes,
+        vec![
+            "TitleTitleTitle01",
+            "DescriptionDesc01",
+            "FieldNameField01",
+            "FieldValueValue01",
+            "FooterFooterFoo01",
+        ]
+    );
+}
+
+#[test]
+fn edits_are_scanned_with_their_embeds() {
+    let codes = codes_in_edit(json!({
+        "content": "edited: discord.gift/EditedContentEd01 and discord.gift/SharedSharedSha01",
+        "embeds": [
+            {"type": "rich", "description": "discord.gift/SharedSharedSha01"},
+            {"type": "rich", "title": "discord.gift/SecondEmbedEmb01"},
+        ],
+    }));
+
+    // The content comes first, and a code that's in an embed too is only found once.
+    assert_eq!(
+        codes,
+        vec!["EditedContentEd01", "SharedSharedSha01", "SecondEmbedEmb01"]
+    );
+}
+
+#[test]
+fn edits_that_only_change_embeds_are_scanned() {
+    let codes = codes_in_edit(json!({
+        "embeds": [{"type": "rich", "url": "https://discord.gift/EmbedOnlyEmbed01"}],
+    }));
+    assert_eq!(codes, vec!["EmbedOnlyEmbed01"]);
+
+    assert!(codes_in_edit(json!({})).is_empty());
+}
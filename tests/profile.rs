@@ -0,0 +1,34 @@
+mod common;
+
+use hyper::{Body, Response};
+use rust_nitro_sniper::connection::build_client;
+use rust_nitro_sniper::discord::{get_profile_for_token, ProfileError};
+use rust_nitro_sniper::token::SecretToken;
+use serde_json::json;
+
+async fn profile_from(token: &str, body: &'static str) -> Result<String, ProfileError> {
+    let address = common::serve(move |_| Response::new(Body::from(body)));
+    let config = common::config_for(address, json!({}));
+    get_profile_for_token(&SecretToken::new(token), &build_client(false), &config)
+        .await
+        .map(|profile| profile.to_string())
+}
+
+#[tokio::test]
+async fn profiles_are_read() {
+    let profile = profile_from(
+        "profile-ok",
+        r#"{"id": "80351110224678912", "username": "nelly", "discriminator": "1337", "avatar": null}"#,
+    )
+    .await;
+    assert_eq!(profile.ok().as_deref(), Some("nelly#1337"));
+}
+
+#[tokio::test]
+async fn unreadable_profiles_are_an_error() {
+    let not_json = profile_from("profile-html", "<html>Hello from staging</html>").await;
+    assert!(matches!(not_json, Err(ProfileError::Malformed)));
+
+    let partial = profile_from("profile-partial", r#"{"id": "80351110224678912", "user"#).await;
+    assert!(matches!(partial, Err(ProfileError::Malformed)));
+}
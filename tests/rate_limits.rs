@@ -0,0 +1,139 @@
+mod common;
+
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+use rust_nitro_sniper::connection;
+use rust_nitro_sniper::discord::{redeem_code_with_retries, Profile, RateLimiter, Route};
+use rust_nitro_sniper::logging::LogBlock;
+use rust_nitro_sniper::redeem::{rate_limit_wait, RedeemOutcome};
+use rust_nitro_sniper::token::SecretToken;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+#[test]
+fn bogus_waits_are_rejected_or_capped() {
+    assert_eq!(rate_limit_wait(1.5), Some(Duration::from_millis(1500)));
+    assert_eq!(rate_limit_wait(-1_f64), None);
+    assert_eq!(rate_limit_wait(f64::NAN), None);
+    assert_eq!(rate_limit_wait(f64::INFINITY), None);
+    assert_eq!(rate_limit_wait(1e300), rate_limit_wait(1e10));
+    assert!(rate_limit_wait(1e300).unwrap() <= Duration::from_secs(60 * 60));
+}
+
+#[test]
+fn huge_reset_after_does_not_panic() {
+    let limiter = RateLimiter::default();
+    let token = SecretToken::new("a");
+    limiter.update(
+        &token,
+        Route::Redeem,
+        &headers(&[
+            ("X-RateLimit-Remaining", "0"),
+            ("X-RateLimit-Reset-After", "1e300"),
+        ]),
+        None,
+    );
+    let budget = limiter.budget(&token, Route::Redeem).unwrap();
+    assert_eq!(budget.remaining, 0);
+    assert!(budget.resets_in <= Duration::from_secs(60 * 60));
+}
+
+#[tokio::test]
+async fn global_limit_only_blocks_its_own_token() {
+    let limiter = RateLimiter::default();
+    let limited = SecretToken::new("limited");
+    let other = SecretToken::new("other");
+    limiter.update(
+        &limited,
+        Route::Redeem,
+        &headers(&[("X-RateLimit-Global", "true")]),
+        Some(Duration::from_secs(60)),
+    );
+
+    let quick = Duration::from_millis(100);
+    assert!(timeout(quick, limiter.acquire(&other, Route::Redeem))
+        .await
+        .is_ok());
+    assert!(timeout(quick, limiter.acquire(&limited, Route::Profile))
+        .await
+        .is_err());
+}
+
+/// Answers the first `limited` redeem requests with a 429 asking to wait `retry_after`
+/// seconds, and every later one with a 200. Returns how many requests were made.
+async fn redeem_through_rate_limits(
+    token: &str,
+    limited: usize,
+    retry_after: f64,
+) -> (RedeemOutcome, usize) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let address = common::serve(move |_| {
+        if counter.fetch_add(1, Ordering::SeqCst) < limited {
+            let body = serde_json::json!({
+                "message": "You are being rate limited.",
+                "retry_after": retry_after,
+                "global": false,
+            });
+            Response::builder()
+                .status(StatusCode::TOO_MANY_REQUESTS)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        } else {
+            Response::new(Body::from("{}"))
+        }
+    });
+    // Every test gets its own token, so their rate limits don't mix.
+    let config = common::config_for(
+        address,
+        serde_json::json!({"main_token": token, "redeem_timeout_ms": 1_000}),
+    );
+    let profile: Profile = serde_json::from_value(serde_json::json!({
+        "username": "sniper",
+        "discriminator": "0001",
+        "avatar": null,
+        "id": "1",
+    }))
+    .unwrap();
+    let mut log = LogBlock::new(&profile);
+
+    let client = connection::build_client(false);
+    let outcome = redeem_code_with_retries("code", &client, &config, &mut log).await;
+    (outcome, requests.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn rate_limited_codes_are_tried_again_after_the_wait() {
+    let started = Instant::now();
+    let (outcome, requests) = redeem_through_rate_limits("retried", 1, 0.2).await;
+
+    assert_eq!(outcome, RedeemOutcome::Claimed);
+    assert_eq!(requests, 2);
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn waits_longer_than_the_redeem_timeout_are_not_retried() {
+    let (outcome, requests) = redeem_through_rate_limits("long-wait", 1, 60_f64).await;
+
+    assert!(matches!(outcome, RedeemOutcome::RateLimited { .. }));
+    assert_eq!(requests, 1);
+}
+
+#[tokio::test]
+async fn redeeming_gives_up_after_a_few_attempts() {
+    let (outcome, requests) = redeem_through_rate_limits("always-limited", usize::MAX, 0.01).await;
+
+    assert!(matches!(outcome, RedeemOutcome::RateLimited { .. }));
+    assert_eq!(requests, 3);
+}
@@ -0,0 +1,104 @@
+use hyper::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+use hyper::StatusCode;
+use rust_nitro_sniper::redeem::RedeemOutcome;
+use std::time::Duration;
+
+fn outcome(status: u16, body: Option<&str>) -> RedeemOutcome {
+    outcome_with_headers(status, HeaderMap::new(), body)
+}
+
+fn outcome_with_headers(status: u16, headers: HeaderMap, body: Option<&str>) -> RedeemOutcome {
+    RedeemOutcome::from_response(
+        StatusCode::from_u16(status).unwrap(),
+        &headers,
+        body.map(str::to_string),
+    )
+}
+
+fn retry_after_header(seconds: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_str(seconds).unwrap());
+    headers
+}
+
+#[test]
+fn discord_error_codes_win_over_the_status() {
+    let unknown = r#"{"message": "Unknown Gift Code", "code": 10038}"#;
+    let redeemed = r#"{"message": "This gift has been redeemed already.", "code": 50050}"#;
+
+    assert_eq!(outcome(404, Some(unknown)), RedeemOutcome::UnknownCode);
+    assert_eq!(outcome(400, Some(unknown)), RedeemOutcome::UnknownCode);
+    assert_eq!(outcome(400, Some(redeemed)), RedeemOutcome::AlreadyRedeemed);
+    assert_eq!(outcome(403, Some(redeemed)), RedeemOutcome::AlreadyRedeemed);
+}
+
+#[test]
+fn the_status_decides_without_a_usable_body() {
+    assert_eq!(outcome(200, None), RedeemOutcome::Claimed);
+    assert_eq!(outcome(404, None), RedeemOutcome::UnknownCode);
+    assert_eq!(outcome(400, None), RedeemOutcome::AlreadyRedeemed);
+    assert_eq!(
+        outcome(404, Some("<html>Not Found</html>")),
+        RedeemOutcome::UnknownCode
+    );
+    assert_eq!(
+        outcome(400, Some(r#"{"code": 50050"#)),
+        RedeemOutcome::AlreadyRedeemed
+    );
+    assert_eq!(outcome(401, None), RedeemOutcome::Unauthorized);
+}
+
+#[test]
+fn retry_after_comes_from_the_header_or_the_body() {
+    let body = r#"{"message": "You are being rate limited.", "retry_after": 1.5, "global": false}"#;
+
+    assert_eq!(
+        outcome_with_headers(429, retry_after_header("2"), None),
+        RedeemOutcome::RateLimited {
+            retry_after: Some(Duration::from_secs(2))
+        }
+    );
+    assert_eq!(
+        outcome(429, Some(body)),
+        RedeemOutcome::RateLimited {
+            retry_after: Some(Duration::from_millis(1500))
+        }
+    );
+    // The header is what Discord's docs tell clients to follow.
+    assert_eq!(
+        outcome_with_headers(429, retry_after_header("2"), Some(body)),
+        RedeemOutcome::RateLimited {
+            retry_after: Some(Duration::from_secs(2))
+        }
+    );
+    assert_eq!(
+        outcome(429, None),
+        RedeemOutcome::RateLimited { retry_after: None }
+    );
+}
+
+#[test]
+fn server_errors_and_unexpected_statuses() {
+    assert_eq!(
+        outcome(502, None),
+        RedeemOutcome::ServerError(StatusCode::BAD_GATEWAY)
+    );
+    assert_eq!(
+        outcome(503, Some(r#"{"message": "upstream", "code": 0}"#)),
+        RedeemOutcome::ServerError(StatusCode::SERVICE_UNAVAILABLE)
+    );
+    assert_eq!(
+        outcome(418, Some("short and stout")),
+        RedeemOutcome::UnknownStatus {
+            status: StatusCode::IM_A_TEAPOT,
+            body: Some("short and stout".to_string())
+        }
+    );
+    assert_eq!(
+        outcome(409, None),
+        RedeemOutcome::UnknownStatus {
+            status: StatusCode::CONFLICT,
+            body: None
+        }
+    );
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use hyper::{Body, Response};
+use rust_nitro_sniper::connection::build_client;
+use rust_nitro_sniper::discord::redeem_code;
+use rust_nitro_sniper::logging::Timeline;
+use rust_nitro_sniper::redeem::RedeemOutcome;
+use rust_nitro_sniper::stats::{ClaimTimings, StatsFile};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[test]
+fn timings_are_appended_as_json_lines() {
+    let path = std::env::temp_dir().join(format!("rns-test-stats-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut timeline = Timeline::new();
+    timeline.mark("detect");
+    timeline.mark("rate limit");
+    let stats = StatsFile::open(&path).unwrap_or_else(|_| panic!("couldn't open the stats file"));
+    stats.record(&ClaimTimings::new("code1", "claimed", &timeline));
+    stats.record(&ClaimTimings::new("code2", "timed_out", &timeline));
+
+    let contents = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let lines = contents
+        .lines()
+        .map(|l| serde_json::from_str::<ClaimTimings>(l).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].code, "code1");
+    assert_eq!(lines[1].outcome, "timed_out");
+    let phases = lines[0]
+        .phases
+        .iter()
+        .map(|p| p.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(phases, vec!["detect", "rate limit"]);
+    let phase_total: f64 = lines[0].phases.iter().map(|p| p.ms).sum();
+    assert!(phase_total <= lines[0].total_ms);
+}
+
+#[test]
+fn phases_can_end_before_they_are_marked() {
+    let mut timeline = Timeline::new();
+    let ended = Instant::now();
+    std::thread::sleep(Duration::from_millis(5));
+    timeline.mark_at("first", ended);
+    timeline.mark("second");
+    // A phase can't end before the one before it, so this one is empty.
+    timeline.mark_at("late", ended);
+
+    let spans = timeline.spans();
+    assert_eq!(spans[1].0, "second");
+    assert!(spans[1].1 >= Duration::from_millis(5));
+    assert_eq!(spans[2].1, Duration::from_secs(0));
+    let total: Duration = spans.iter().map(|(_, d)| *d).sum();
+    assert!(total <= timeline.elapsed());
+}
+
+#[tokio::test]
+async fn redeeming_marks_when_the_request_was_sent() {
+    let received = Arc::new(Mutex::new(None));
+    let recorded = received.clone();
+    let address = common::serve(move |request| {
+        let length = request.headers().get("Content-Length").cloned();
+        *recorded.lock().unwrap() = length;
+        Response::new(Body::from("{}"))
+    });
+    let config = common::config_for(address, serde_json::json!({"main_token": "timed"}));
+
+    let mut timeline = Timeline::new();
+    let outcome = redeem_code("code", &build_client(false), &config, &mut timeline).await;
+
+    assert_eq!(outcome, RedeemOutcome::Claimed);
+    assert_eq!(
+        received.lock().unwrap().as_ref().unwrap().to_str().unwrap(),
+        "2"
+    );
+    let phases = timeline
+        .spans()
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        phases,
+        vec!["rate limit", "request sent", "response headers", "body"]
+    );
+}
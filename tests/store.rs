@@ -0,0 +1,187 @@
+use rust_nitro_sniper::config::Config;
+use rust_nitro_sniper::store::{FileStore, MemoryStore, SeenCode, SeenCodeStore};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+fn seen(code: &str) -> SeenCode {
+    SeenCode::new(code.to_string(), "test#0000".to_string())
+}
+
+fn seen_hours_ago(code: &str, hours: i64) -> SeenCode {
+    let mut entry = seen(code);
+    entry.seen_at -= hours * 60 * 60;
+    entry
+}
+
+fn codes(store: &MemoryStore) -> Vec<&str> {
+    store.entries().map(|e| e.code.as_str()).collect()
+}
+
+fn store_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rns-test-{}-{}.jsonl", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn open_file_store(path: &Path) -> FileStore {
+    FileStore::open(path, 10, HOUR).unwrap_or_else(|_| panic!("couldn't open the store"))
+}
+
+fn lines(path: &Path) -> Vec<SeenCode> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+#[test]
+fn oldest_codes_are_dropped_over_capacity() {
+    let mut store = MemoryStore::new(2, HOUR);
+    store.insert(seen("a"));
+    store.insert(seen("b"));
+    store.insert(seen("c"));
+
+    assert_eq!(store.len(), 2);
+    assert!(!store.contains("a"));
+    assert_eq!(codes(&store), vec!["b", "c"]);
+}
+
+#[test]
+fn inserting_again_moves_a_code_to_the_back() {
+    let mut store = MemoryStore::new(2, HOUR);
+    store.insert(seen("a"));
+    store.insert(seen("b"));
+    store.insert(seen("a"));
+    // The first position of "a" is stale now, so "b" is the oldest code.
+    store.insert(seen("c"));
+
+    assert_eq!(store.len(), 2);
+    assert!(store.contains("a"));
+    assert!(!store.contains("b"));
+    assert_eq!(codes(&store), vec!["a", "c"]);
+}
+
+#[test]
+fn expired_codes_are_forgotten() {
+    let mut store = MemoryStore::new(10, HOUR);
+    store.insert(seen_hours_ago("old", 2));
+    assert!(!store.contains("old"));
+
+    store.insert(seen("new"));
+    assert!(store.contains("new"));
+    assert_eq!(codes(&store), vec!["new"]);
+}
+
+#[test]
+fn huge_ttl_never_expires() {
+    let mut store = MemoryStore::new(10, Duration::from_secs(u64::MAX));
+    store.insert(seen_hours_ago("old", 24 * 365));
+    assert!(store.contains("old"));
+}
+
+#[test]
+fn overflowing_ttl_is_a_config_problem() {
+    let config: Config = serde_json::from_str(&format!(
+        r#"{{"snipe_on_main_token": true, "guild_blacklist": [], "seen_codes_ttl_hours": {}}}"#,
+        u64::MAX
+    ))
+    .unwrap();
+
+    assert_eq!(config.seen_codes_ttl(), Duration::from_secs(u64::MAX));
+    assert!(config
+        .validate()
+        .iter()
+        .any(|p| p.path == "$.seen_codes_ttl_hours"));
+}
+
+#[test]
+fn seen_codes_survive_a_restart() {
+    let path = store_path("restart");
+    {
+        let mut store = open_file_store(&path);
+        store.insert(seen("a"));
+        store.insert(seen("b"));
+        store.record_outcome("a", "claimed");
+    }
+
+    let store = open_file_store(&path);
+    assert!(store.contains("a"));
+    assert!(store.contains("b"));
+    assert!(!store.contains("c"));
+
+    // Opening compacts the file, and the last entry of a code is the one that counts.
+    let entries = lines(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(entries.len(), 2);
+    let a = entries.iter().find(|e| e.code == "a").unwrap();
+    assert_eq!(a.outcome.as_deref(), Some("claimed"));
+}
+
+#[test]
+fn compacting_rewrites_the_file_through_a_temp_file() {
+    let path = store_path("compact");
+    let mut store = open_file_store(&path);
+    store.insert(seen("a"));
+    store.record_outcome("a", "unknown_code");
+    store.insert(seen("b"));
+    assert_eq!(lines(&path).len(), 3);
+
+    store.compact();
+    assert!(!path.with_extension("tmp").exists());
+    assert_eq!(lines(&path).len(), 2);
+
+    // New codes still go to the compacted file, not the old one.
+    store.insert(seen("c"));
+    let entries = lines(&path);
+    fs::remove_file(&path).unwrap();
+    let codes = entries.iter().map(|e| e.code.as_str()).collect::<Vec<_>>();
+    assert_eq!(codes, vec!["a", "b", "c"]);
+}
+
+#[test]
+fn a_torn_last_line_is_skipped() {
+    let path = store_path("torn");
+    let complete = serde_json::to_string(&seen("a")).unwrap();
+    fs::write(&path, format!("{}\n{{\"code\": \"b\", \"seen_a", complete)).unwrap();
+
+    let store = open_file_store(&path);
+    assert!(store.contains("a"));
+    assert!(!store.contains("b"));
+
+    let entries = lines(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn forgotten_codes_can_be_seen_again() {
+    let mut store = MemoryStore::new(10, HOUR);
+    store.insert(seen("a"));
+    store.insert(seen("b"));
+    store.forget("a");
+    assert!(!store.contains("a"));
+    assert_eq!(codes(&store), vec!["b"]);
+
+    store.insert(seen("a"));
+    assert_eq!(codes(&store), vec!["b", "a"]);
+}
+
+#[test]
+fn forgotten_codes_stay_forgotten_after_a_restart() {
+    let path = store_path("forget");
+    {
+        let mut store = open_file_store(&path);
+        store.insert(seen("a"));
+        store.insert(seen("b"));
+        store.forget("a");
+    }
+
+    let store = open_file_store(&path);
+    fs::remove_file(&path).unwrap();
+    assert!(!store.contains("a"));
+    assert!(store.contains("b"));
+}
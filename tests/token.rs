@@ -0,0 +1,59 @@
+use rust_nitro_sniper::config::Config;
+use rust_nitro_sniper::token::SecretToken;
+
+const RAW: &str = "NzA5MzY4OTE3NTQzMzY0NjA4.Xrj7kg.not-a-real-token-but-shaped-like-one";
+const WEBHOOK: &str = "https://discord.com/api/webhooks/123456789012345678/webhook-secret";
+
+#[test]
+fn formatting_never_shows_the_raw_token() {
+    for token in &[
+        SecretToken::new(RAW),
+        SecretToken::new(RAW).with_label("alt"),
+    ] {
+        for shown in &[
+            format!("{:?}", token),
+            format!("{}", token),
+            format!("{:#?}", token),
+        ] {
+            assert!(!shown.contains(RAW), "{} shows the raw token", shown);
+            assert!(shown.contains(&token.label()));
+        }
+    }
+}
+
+#[test]
+fn unlabelled_tokens_fall_back_to_their_fingerprint() {
+    let label = SecretToken::new(RAW).label();
+    let fingerprint = label.strip_prefix("token-").unwrap();
+    assert_eq!(fingerprint.len(), 8);
+    assert!(fingerprint
+        .chars()
+        .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+
+    // The fingerprint stays the same across restarts and differs between tokens.
+    assert_eq!(SecretToken::new(RAW).label(), label);
+    assert_ne!(SecretToken::new("another-token").label(), label);
+    assert_eq!(SecretToken::new(RAW).with_label("alt").label(), "alt");
+}
+
+#[test]
+fn config_debug_dumps_never_show_secrets() {
+    let config: Config = serde_json::from_value(serde_json::json!({
+        "main_token": RAW,
+        "snipe_on_main_token": true,
+        "sub_tokens": [format!("{}-sub", RAW)],
+        "webhook": WEBHOOK,
+        "guild_blacklist": [],
+    }))
+    .unwrap();
+
+    let dump = format!("{:?}", config);
+    assert!(!dump.contains(RAW), "{} shows a raw token", dump);
+    assert!(
+        !dump.contains("webhook-secret"),
+        "{} shows the webhook",
+        dump
+    );
+    assert!(dump.contains(&config.main_token().label()));
+    assert!(dump.contains("snipe_on_main_token: true"));
+}
@@ -0,0 +1,135 @@
+use rust_nitro_sniper::token::SecretToken;
+use rust_nitro_sniper::vault::{Vault, VaultError};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+fn vault_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rns-test-{}-{}.vault", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn new_vault(path: &Path) -> Vault {
+    Vault::open_or_create(path, PASSPHRASE.to_string())
+        .unwrap_or_else(|_| panic!("couldn't create the vault"))
+}
+
+fn labels(vault: &Vault) -> Vec<&str> {
+    vault.entries().iter().map(|e| e.label.as_str()).collect()
+}
+
+fn vault_with_kdf(name: &str, mem_cost: u32, time_cost: u32, lanes: u32) -> PathBuf {
+    let path = vault_path(name);
+    let file = format!(
+        r#"{{
+            "version": 1,
+            "kdf": {{"mem_cost": {}, "time_cost": {}, "lanes": {}}},
+            "salt": "AAAAAAAAAAAAAAAAAAAAAA==",
+            "nonce": "AAAAAAAAAAAAAAAA",
+            "ciphertext": "AAAA"
+        }}"#,
+        mem_cost, time_cost, lanes
+    );
+    fs::write(&path, file).unwrap();
+    path
+}
+
+fn is_unsafe_kdf(path: PathBuf) -> bool {
+    let result = Vault::open(&path, "passphrase".to_string());
+    fs::remove_file(&path).unwrap();
+    matches!(result.err(), Some(VaultError::UnsafeKdf))
+}
+
+#[test]
+fn weak_kdf_parameters_are_refused() {
+    assert!(is_unsafe_kdf(vault_with_kdf("weak-memory", 8, 3, 1)));
+    assert!(is_unsafe_kdf(vault_with_kdf("weak-time", 64 * 1024, 1, 1)));
+}
+
+#[test]
+fn huge_kdf_parameters_are_refused() {
+    assert!(is_unsafe_kdf(vault_with_kdf("huge-memory", u32::MAX, 3, 1)));
+    assert!(is_unsafe_kdf(vault_with_kdf(
+        "huge-time",
+        64 * 1024,
+        u32::MAX,
+        1
+    )));
+    assert!(is_unsafe_kdf(vault_with_kdf("no-lanes", 64 * 1024, 3, 0)));
+}
+
+#[test]
+fn saved_vaults_open_with_the_same_tokens() {
+    let path = vault_path("round-trip");
+    let mut vault = new_vault(&path);
+    vault
+        .add("main".to_string(), SecretToken::new("main-token"), true)
+        .unwrap_or_else(|_| panic!("couldn't add the main token"));
+    vault
+        .add("alt".to_string(), SecretToken::new("alt-token"), false)
+        .unwrap_or_else(|_| panic!("couldn't add the alt token"));
+    vault
+        .save()
+        .unwrap_or_else(|_| panic!("couldn't save the vault"));
+
+    let raw = fs::read_to_string(&path).unwrap();
+    assert!(!raw.contains("main-token") && !raw.contains("alt-token"));
+
+    let opened = Vault::open(&path, PASSPHRASE.to_string());
+    let wrong = Vault::open(&path, "incorrect horse".to_string());
+    fs::remove_file(&path).unwrap();
+
+    let opened = opened.unwrap_or_else(|_| panic!("couldn't open the saved vault"));
+    assert_eq!(labels(&opened), vec!["main", "alt"]);
+    assert_eq!(opened.main_label(), Some("main"));
+    assert_eq!(opened.main_token().unwrap().expose(), "main-token");
+    assert_eq!(opened.main_token().unwrap().label(), "main");
+    let sub_tokens = opened.sub_tokens();
+    assert_eq!(sub_tokens.len(), 1);
+    assert_eq!(sub_tokens[0].expose(), "alt-token");
+
+    assert!(matches!(wrong.err(), Some(VaultError::WrongPassphrase)));
+}
+
+#[cfg(unix)]
+#[test]
+fn vault_files_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = vault_path("permissions");
+    new_vault(&path)
+        .save()
+        .unwrap_or_else(|_| panic!("couldn't save the vault"));
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(!path.with_extension("tmp").exists());
+}
+
+#[test]
+fn labels_are_unique_and_removable() {
+    let mut vault = new_vault(&vault_path("labels"));
+    assert!(vault
+        .add("main".to_string(), SecretToken::new("main-token"), true)
+        .is_ok());
+    assert!(vault
+        .add("alt".to_string(), SecretToken::new("alt-token"), false)
+        .is_ok());
+
+    let duplicate = vault.add("alt".to_string(), SecretToken::new("other-token"), false);
+    assert!(matches!(duplicate, Err(VaultError::DuplicateLabel(label)) if label == "alt"));
+    assert_eq!(labels(&vault), vec!["main", "alt"]);
+
+    let unknown = vault.remove("nope");
+    assert!(matches!(unknown, Err(VaultError::UnknownLabel(label)) if label == "nope"));
+
+    // Removing the main token leaves no main token, rather than promoting another one.
+    assert!(vault.remove("main").is_ok());
+    assert_eq!(labels(&vault), vec!["alt"]);
+    assert_eq!(vault.main_label(), None);
+    assert!(vault.main_token().is_none());
+    assert_eq!(vault.sub_tokens().len(), 1);
+}